@@ -5,7 +5,7 @@ const search = await fourFours();
 
 export const App = memo(() => {
     const [numbers, setNumbers] = useState<string>("");
-    const [result, setResult] = useState<string[]>([]);
+    const [result, setResult] = useState<[number, string][]>([]);
     const [isLoading, setIsLoading] = useState(false);
     const updateResult = async (numbers: string) => {
         setIsLoading(true);
@@ -16,7 +16,7 @@ export const App = memo(() => {
             setResult([]);
             try {
                 const result = await search(numbers);
-                setResult([...Array(1001).keys()].map((i) => (result.get(i) ? `${i} = ${result.get(i)}` : undefined)).filter((s) => !!s).map((s) => s as string));
+                setResult([...Array(1001).keys()].filter((i) => result.has(i)).map((i) => [i, result.get(i)] as [number, string]));
             } catch (error: any) {
                 console.error(error.toString());
                 setResult([]);
@@ -33,7 +33,7 @@ export const App = memo(() => {
             <input type="submit" value="Calc" />
         </form>
         <ul>
-            {result.map((result, i) => (<li key={i}>{result}</li>))}
+            {result.map(([n, mathml]) => (<li key={n}>{n} = <span dangerouslySetInnerHTML={{ __html: mathml }} /></li>))}
         </ul>
         {isLoading && "Loading..."}
     </>;
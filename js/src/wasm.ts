@@ -5,7 +5,7 @@ async function initFourFours() {
     // await fourFours.default(); // init
     // await fourFours.initThreadPool(navigator.hardwareConcurrency);
     fourFours.initLog();
-    return Comlink.proxy(fourFours.searchWasm);
+    return Comlink.proxy(fourFours.searchWasmMathML);
 }
 
 Comlink.expose(initFourFours);
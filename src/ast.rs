@@ -0,0 +1,160 @@
+//! `Equation`の構文木。途中の値とコストを各節に持ち、JSONとして書き出せる
+//!
+//! ```json
+//! {"op": "add", "args": [{"number": "4", "value": 4, "cost": 0}, ...], "value": 8, "cost": 1}
+//! ```
+
+use crate::{
+    evaluate::EvaluationError,
+    postfix::{self, Arity, Visitor},
+    search::{BinaryOp, Equation, Token, UnaryOp, Value},
+};
+use serde::{Serialize, Serializer};
+use std::collections::HashMap;
+
+/// 構文木の節
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum Node {
+    /// 数値（`44`や`.4`）
+    Number {
+        number: String,
+        #[serde(serialize_with = "serialize_value")]
+        value: Value,
+        cost: u8,
+    },
+    /// 演算子の適用
+    Operator {
+        /// 演算子名
+        op: String,
+        args: Vec<Node>,
+        #[serde(serialize_with = "serialize_value")]
+        value: Value,
+        /// この部分式全体のコスト
+        cost: u8,
+    },
+}
+
+impl Node {
+    pub fn value(&self) -> Value {
+        match self {
+            Node::Number { value, .. } | Node::Operator { value, .. } => *value,
+        }
+    }
+
+    pub fn cost(&self) -> u8 {
+        match self {
+            Node::Number { cost, .. } | Node::Operator { cost, .. } => *cost,
+        }
+    }
+
+    /// JSON文字列にする
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+/// 整数値は整数として書き出す（`8.0`ではなく`8`）
+fn serialize_value<S: Serializer>(value: &Value, serializer: S) -> Result<S::Ok, S::Error> {
+    if value.fract() == 0f64 && value.abs() < (1u64 << 53) as Value {
+        serializer.serialize_i64(*value as i64)
+    } else {
+        serializer.serialize_f64(*value)
+    }
+}
+
+/// トークン列から構文木を作る。演算子を適用し直して途中の値を求める
+#[derive(Debug, Clone)]
+pub struct AstBuilder {
+    unary_ops: HashMap<Token, (String, UnaryOp)>,
+    binary_ops: HashMap<Token, (String, BinaryOp)>,
+}
+
+impl AstBuilder {
+    pub fn new(
+        unary_ops: HashMap<Token, (String, UnaryOp)>,
+        binary_ops: HashMap<Token, (String, BinaryOp)>,
+    ) -> AstBuilder {
+        AstBuilder {
+            unary_ops,
+            binary_ops,
+        }
+    }
+
+    /// 演算子を適用できない項がある場合など、探索で作られうる式でなければエラーを返す
+    pub fn build(&self, equation: &Equation) -> Result<Node, EvaluationError> {
+        postfix::walk(&equation.tokens, self)
+    }
+}
+
+/// 部分式の節を組み立てる
+impl Visitor for AstBuilder {
+    type Output = Node;
+    type Error = EvaluationError;
+
+    fn arity(&self, token: Token) -> Option<Arity> {
+        if self.unary_ops.contains_key(&token) {
+            Some(Arity::Unary)
+        } else if self.binary_ops.contains_key(&token) {
+            Some(Arity::Binary)
+        } else {
+            None
+        }
+    }
+
+    fn number(&self, digits: &str, decimal: bool) -> Result<Node, EvaluationError> {
+        // 小数は`Equation::from_decimal`と同じく値とコストを決める
+        Ok(if decimal {
+            Node::Number {
+                number: ".".to_string() + digits,
+                value: format!("0.{}", digits).parse().unwrap(),
+                cost: 1,
+            }
+        } else {
+            Node::Number {
+                number: digits.to_string(),
+                value: digits.parse().unwrap(),
+                cost: 0,
+            }
+        })
+    }
+
+    fn unary(&self, position: usize, token: Token, arg: Node) -> Result<Node, EvaluationError> {
+        let (name, op) = self
+            .unary_ops
+            .get(&token)
+            .ok_or(EvaluationError::UnknownToken(position, token))?;
+        let value = (op.apply)(arg.value())
+            .ok_or_else(|| EvaluationError::NotApplicable(position, name.clone()))?;
+        Ok(Node::Operator {
+            op: name.clone(),
+            value,
+            cost: arg.cost().saturating_add(op.cost),
+            args: vec![arg],
+        })
+    }
+
+    fn binary(
+        &self,
+        position: usize,
+        token: Token,
+        arg1: Node,
+        arg2: Node,
+    ) -> Result<Node, EvaluationError> {
+        let (name, op) = self
+            .binary_ops
+            .get(&token)
+            .ok_or(EvaluationError::UnknownToken(position, token))?;
+        let value = (op.apply)(arg1.value(), arg2.value())
+            .ok_or_else(|| EvaluationError::NotApplicable(position, name.clone()))?;
+        Ok(Node::Operator {
+            op: name.clone(),
+            value,
+            cost: arg1
+                .cost()
+                .saturating_add(arg2.cost())
+                .saturating_add(op.cost),
+            args: vec![arg1, arg2],
+        })
+    }
+}
@@ -1,82 +1,260 @@
-use std::env;
+use std::{env, fmt, fs, process};
 
 use four_fours::{
-    print::{BinaryOpPrinter, Printer, UnaryOpPrinter},
-    search::{BinaryOp, UnaryOp},
-    search_int,
+    ast::Node,
+    evaluate::Verification,
+    preset::Preset,
+    print::{Parenthesization, PrintError, PrintStyle},
+    rules,
+    search::Equation,
 };
+use serde::Serialize;
+
+/// 結果の出力形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// `n = 式`の形式で1行ずつ
+    Text,
+    /// LaTeXの表
+    Latex,
+    /// `n = 式`の形式で、式をMathMLの`<math>`要素で1行ずつ
+    Mathml,
+    /// `n = 式`の形式で、式を逆ポーランド記法で1行ずつ
+    Rpn,
+    /// `n = 式`の形式で、式をS式で1行ずつ
+    Sexp,
+    /// 式とその構文木をまとめたJSON
+    Json,
+    /// 分数や根号、指数を複数行で表示した`n = 式`を1つずつ
+    Pretty,
+}
+
+/// `--format json`で出力する表
+#[derive(Serialize)]
+struct JsonTable<'a> {
+    numbers: &'a str,
+    preset: &'a str,
+    results: Vec<JsonRow>,
+}
+
+#[derive(Serialize)]
+struct JsonRow {
+    n: i32,
+    expression: String,
+    tree: Node,
+}
+
+/// 出力形式に応じて式を表示する関数
+type PrintFn = Box<dyn Fn(&Equation) -> Result<String, PrintError>>;
+
+/// 表示できない式は、その理由を標準エラー出力に報告して飛ばす
+fn or_report<T>(n: i32, result: Result<T, impl fmt::Display>) -> Option<T> {
+    result
+        .map_err(|e| eprintln!("Cannot print the equation for {}: {}", n, e))
+        .ok()
+}
 
 fn main() {
     env_logger::init();
-    let args = env::args().skip(1).collect::<Vec<_>>();
-    let numbers: &str;
-    if let Some(arg) = args.first() {
-        if arg.chars().all(|c| c.is_ascii_digit()) {
-            numbers = arg;
-        } else {
-            eprintln!("Given arg contains invalid character: {}", arg);
-            numbers = "4444";
+    let mut numbers = "4444".to_string();
+    let mut show_stats = false;
+    let mut verify = false;
+    let mut style = PrintStyle::Standard;
+    let mut parenthesization = Parenthesization::Minimal;
+    let mut format = Format::Text;
+    let mut preset = Preset::standard();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--stats" => show_stats = true,
+            "--verify" => verify = true,
+            "--ascii" => style = PrintStyle::Ascii,
+            "--pretty" => format = Format::Pretty,
+            "--style" => {
+                let name = args.next().unwrap_or_default();
+                style = PrintStyle::by_name(&name).unwrap_or_else(|| {
+                    eprintln!(
+                        "Unknown style: {} (available: {})",
+                        name,
+                        PrintStyle::NAMES.join(", ")
+                    );
+                    process::exit(1);
+                });
+            }
+            "--parens" => {
+                let name = args.next().unwrap_or_default();
+                parenthesization = Parenthesization::by_name(&name).unwrap_or_else(|| {
+                    eprintln!(
+                        "Unknown parenthesization: {} (available: {})",
+                        name,
+                        Parenthesization::NAMES.join(", ")
+                    );
+                    process::exit(1);
+                });
+            }
+            "--format" => {
+                let name = args.next().unwrap_or_default();
+                format = match name.as_str() {
+                    "text" => Format::Text,
+                    "latex" => Format::Latex,
+                    "mathml" => Format::Mathml,
+                    "rpn" => Format::Rpn,
+                    "sexp" => Format::Sexp,
+                    "json" => Format::Json,
+                    "pretty" => Format::Pretty,
+                    _ => {
+                        eprintln!(
+                            "Unknown format: {} (available: text, latex, mathml, rpn, sexp, json, pretty)",
+                            name
+                        );
+                        process::exit(1);
+                    }
+                };
+            }
+            "--preset" => {
+                let name = args.next().unwrap_or_default();
+                preset = Preset::by_name(&name).unwrap_or_else(|| {
+                    eprintln!(
+                        "Unknown preset: {} (available: {})",
+                        name,
+                        Preset::NAMES.join(", ")
+                    );
+                    process::exit(1);
+                });
+            }
+            "--rules" => {
+                let path = args.next().unwrap_or_default();
+                let text = fs::read_to_string(&path).unwrap_or_else(|e| {
+                    eprintln!("Cannot read rules file {}: {}", path, e);
+                    process::exit(1);
+                });
+                preset = rules::parse(&text).unwrap_or_else(|e| {
+                    eprintln!("{}: {}", path, e);
+                    process::exit(1);
+                });
+            }
+            _ if arg.chars().all(|c| c.is_ascii_digit()) => numbers = arg,
+            _ => eprintln!("Given arg contains invalid character: {}", arg),
         }
-    } else {
-        numbers = "4444";
     }
 
-    // 演算子
-    let negate = UnaryOp::new(0x00, 2, |v| Some(-v));
-    let sqrt = UnaryOp::new(0x01, 4, |v| match v {
-        x if x > 0f64 && x != 0f64 && x != 1f64 => Some(v.sqrt()),
-        _ => None,
-    });
-    let fact = UnaryOp::new(0x02, 6, |v| {
-        const TABLE: [f64; 9] = [1., 1., 2., 6., 24., 120., 720., 5040., 40320.];
-        if v < 0f64 || v > (i32::MAX as f64) || v.fract().abs() > f64::EPSILON {
-            return None;
+    // 結果表示
+    let print: PrintFn = match format {
+        Format::Latex => {
+            let printer = preset.latex_printer().parenthesization(parenthesization);
+            Box::new(move |e| printer.print(e))
         }
-        let n = v.round() as usize;
-        if n >= TABLE.len() {
-            return None;
+        Format::Mathml => {
+            let printer = preset.mathml_printer().parenthesization(parenthesization);
+            Box::new(move |e| printer.print(e))
         }
-        Some(TABLE[n])
-    });
-    // binary ops
-    let add = BinaryOp::new(0x10, 1, |v1, v2| Some(v1 + v2));
-    let sub = BinaryOp::new(0x11, 2, |v1, v2| Some(v1 - v2));
-    let mul = BinaryOp::new(0x12, 3, |v1, v2| Some(v1 * v2));
-    let div = BinaryOp::new(
-        0x13,
-        4,
-        |v1, v2| if v2 == 0f64 { None } else { Some(v1 / v2) },
-    );
-    let pow = BinaryOp::new(0x14, 6, |v1, v2| Some(v1.powf(v2)));
-
-    // 結果表示
-    let negate_p = UnaryOpPrinter::new(0x00, "-", "", 3, true);
-    let sqrt_p = UnaryOpPrinter::new(0x01, "√", "", 1, true);
-    let fact_p = UnaryOpPrinter::new(0x02, "", "!", 2, true);
-    let add_p = BinaryOpPrinter::new(0x10, "", "+", "", 6, true, true, true, true);
-    let sub_p = BinaryOpPrinter::new(0x11, "", "-", "", 6, true, false, true, true);
-    let mul_p = BinaryOpPrinter::new(0x12, "", "*", "", 5, true, true, true, true);
-    let div_p = BinaryOpPrinter::new(0x13, "", "/", "", 5, true, false, true, true);
-    let pow_p = BinaryOpPrinter::new(0x14, "", "^", "", 3, false, true, true, true);
-    let printer = Printer::new(
-        vec![negate_p, sqrt_p, fact_p],
-        vec![add_p, sub_p, mul_p, div_p, pow_p],
-        "(",
-        ")",
-    );
+        Format::Rpn => {
+            let printer = preset.rpn_printer();
+            Box::new(move |e| printer.print(e))
+        }
+        Format::Sexp => {
+            let printer = preset.sexp_printer();
+            Box::new(move |e| printer.print(e))
+        }
+        Format::Text | Format::Json | Format::Pretty => {
+            let printer = preset
+                .style_printer(style)
+                .parenthesization(parenthesization);
+            Box::new(move |e| printer.print(e))
+        }
+    };
 
-    let mut results = search_int(
-        vec![negate, sqrt, fact],
-        vec![add, sub, mul, div, pow],
-        numbers,
-    )
-    .into_iter()
-    .map(|(n, result)| (n, printer.print(&result)))
-    .filter(|(n, _)| *n >= 0 && *n <= 1000)
-    .collect::<Vec<_>>();
+    let (results, stats) = preset.search_int(&numbers);
+    let mut results = results
+        .into_iter()
+        .filter(|(n, _)| *n >= 0 && *n <= 1000)
+        .collect::<Vec<_>>();
     results.sort_by_key(|(n, _)| *n);
 
-    for (n, e) in results {
-        println!("{} = {}", n, e);
+    if verify {
+        // 計算し直して厳密な値と比べ、誤差によってのみ一致している結果を報告する
+        let evaluator = preset.evaluator();
+        let (mut exact, mut approximate, mut wrong) = (0, 0, 0);
+        for (n, e) in results.iter() {
+            let expression = || print(e).unwrap_or_else(|_| format!("{:?}", e.tokens));
+            match evaluator.verify(*n, e) {
+                Ok(Verification::Exact) => exact += 1,
+                Ok(Verification::Approximate) => approximate += 1,
+                Ok(v) => {
+                    wrong += 1;
+                    eprintln!("{} = {}: {}", n, expression(), v);
+                }
+                Err(err) => {
+                    wrong += 1;
+                    eprintln!("{} = {}: {}", n, expression(), err);
+                }
+            }
+        }
+        eprintln!(
+            "Verified {} results: {} exact, {} approximate, {} wrong",
+            results.len(),
+            exact,
+            approximate,
+            wrong
+        );
+    }
+
+    if format == Format::Json {
+        let ast_builder = preset.ast_builder();
+        let table = JsonTable {
+            numbers: &numbers,
+            preset: &preset.name,
+            results: results
+                .iter()
+                .filter_map(|(n, e)| {
+                    Some(JsonRow {
+                        n: *n,
+                        expression: or_report(*n, print(e))?,
+                        tree: or_report(*n, ast_builder.build(e))?,
+                    })
+                })
+                .collect(),
+        };
+        println!("{}", serde_json::to_string_pretty(&table).unwrap());
+    } else if results.is_empty() {
+        if let Some(target) = preset.target {
+            println!("{} cannot be made from {}", target, numbers);
+        }
+    }
+    match format {
+        Format::Text | Format::Mathml | Format::Rpn | Format::Sexp => {
+            for (n, e) in results {
+                if let Some(expression) = or_report(n, print(&e)) {
+                    println!("{} = {}", n, expression);
+                }
+            }
+        }
+        Format::Latex => {
+            println!("\\begin{{tabular}}{{r|l}}");
+            println!("$n$ & Equation \\\\");
+            println!("\\hline");
+            for (n, e) in results {
+                if let Some(expression) = or_report(n, print(&e)) {
+                    println!("${}$ & ${}$ \\\\", n, expression);
+                }
+            }
+            println!("\\end{{tabular}}");
+        }
+        Format::Pretty => {
+            let printer = preset
+                .pretty_printer(style)
+                .parenthesization(parenthesization);
+            for (n, e) in results {
+                let label = format!("{} = ", n);
+                if let Some(expression) = or_report(n, printer.print_with_label(&label, &e)) {
+                    println!("{}\n", expression);
+                }
+            }
+        }
+        Format::Json => {}
+    }
+
+    if show_stats {
+        eprint!("{}", stats);
     }
 }
@@ -0,0 +1,369 @@
+//! 探索で求めた`Equation::value`を信用せず、トークン列から値を計算し直す
+//!
+//! 浮動小数点での値に加えて、厳密な計算のできる演算子だけからなる式は有理数で値を求める。
+//! これにより、浮動小数点の誤差によって整数とみなされただけの式を見分けられる
+
+use crate::{
+    postfix::{self, Arity, TokenError, Visitor},
+    search::{BinaryOp, Equation, Rational, Token, UnaryOp, Value},
+};
+use std::{collections::HashMap, error, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvaluationError {
+    /// 演算子として登録されていないトークン
+    UnknownToken(usize, Token),
+    /// 演算子の項が足りない
+    MissingOperand(usize),
+    /// 数値の途中以外に現れた数値二桁目以降のトークンや小数点
+    MisplacedDigit(usize),
+    /// 演算子を項に適用できない（`√(-4)`など）
+    NotApplicable(usize, String),
+    /// 空のトークン列
+    Empty,
+    /// 演算子に使われなかった項が残っている
+    LeftoverOperands(usize),
+}
+
+impl fmt::Display for EvaluationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvaluationError::UnknownToken(position, token) => {
+                write!(f, "Unknown token {:#04x} at {}", token, position)
+            }
+            EvaluationError::MissingOperand(position) => {
+                write!(f, "Operator at {} is missing an operand", position)
+            }
+            EvaluationError::MisplacedDigit(position) => {
+                write!(
+                    f,
+                    "Digit or decimal point at {} does not follow a number",
+                    position
+                )
+            }
+            EvaluationError::NotApplicable(position, name) => {
+                write!(
+                    f,
+                    "Operator `{}` at {} cannot be applied to its operand",
+                    name, position
+                )
+            }
+            EvaluationError::Empty => write!(f, "Empty token sequence"),
+            EvaluationError::LeftoverOperands(n) => {
+                write!(f, "{} operands are left without an operator", n)
+            }
+        }
+    }
+}
+
+impl error::Error for EvaluationError {}
+
+impl From<TokenError> for EvaluationError {
+    fn from(e: TokenError) -> Self {
+        match e {
+            TokenError::UnknownToken(position, token) => {
+                EvaluationError::UnknownToken(position, token)
+            }
+            TokenError::MissingOperand(position) => EvaluationError::MissingOperand(position),
+            TokenError::MisplacedDigit(position) => EvaluationError::MisplacedDigit(position),
+            TokenError::Empty => EvaluationError::Empty,
+            TokenError::LeftoverOperands(n) => EvaluationError::LeftoverOperands(n),
+        }
+    }
+}
+
+/// 計算し直した値
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Evaluation {
+    /// 探索と同じく浮動小数点で求めた値
+    pub value: Value,
+    /// 有理数での厳密な値。厳密に計算できない演算子を含む場合や、途中の値が大きすぎる場合は`None`
+    pub exact: Option<Rational>,
+}
+
+/// 探索結果の検証結果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verification {
+    /// 厳密な値が整数と一致する
+    Exact,
+    /// 厳密な値は求められないが、浮動小数点での値は整数とみなせる
+    Approximate,
+    /// 浮動小数点では整数とみなせるが、厳密な値は異なる（誤差によってのみ一致している）
+    FloatError(Rational),
+    /// 計算し直した浮動小数点での値が、記録された値と異なるか整数とみなせない
+    Mismatch(Value),
+}
+
+impl Verification {
+    /// 整数と一致しないことが確かめられたかどうか
+    pub fn is_wrong(&self) -> bool {
+        matches!(
+            self,
+            Verification::FloatError(_) | Verification::Mismatch(_)
+        )
+    }
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verification::Exact => write!(f, "exact"),
+            Verification::Approximate => write!(f, "approximate"),
+            Verification::FloatError(exact) => write!(f, "float error (exact value is {})", exact),
+            Verification::Mismatch(value) => write!(f, "mismatch (recomputed value is {})", value),
+        }
+    }
+}
+
+/// トークン列を、探索とは独立にスタックで計算し直す
+#[derive(Debug, Clone)]
+pub struct Evaluator {
+    unary_ops: HashMap<Token, (String, UnaryOp)>,
+    binary_ops: HashMap<Token, (String, BinaryOp)>,
+}
+
+impl Evaluator {
+    pub fn new(
+        unary_ops: HashMap<Token, (String, UnaryOp)>,
+        binary_ops: HashMap<Token, (String, BinaryOp)>,
+    ) -> Evaluator {
+        Evaluator {
+            unary_ops,
+            binary_ops,
+        }
+    }
+
+    pub fn evaluate(&self, tokens: &[Token]) -> Result<Evaluation, EvaluationError> {
+        postfix::walk(tokens, self)
+    }
+
+    /// 式が整数`n`と等しいことを確かめる。
+    /// 計算し直した値が記録された値と一致し、厳密な値が求められればそれが`n`と一致するかを調べる
+    pub fn verify(&self, n: i32, equation: &Equation) -> Result<Verification, EvaluationError> {
+        let evaluation = self.evaluate(&equation.tokens)?;
+        if evaluation.value != equation.value || (evaluation.value - n as Value).abs() >= 1e-9 {
+            return Ok(Verification::Mismatch(evaluation.value));
+        }
+        Ok(match evaluation.exact {
+            Some(exact) if exact == Rational::integer(n.into()) => Verification::Exact,
+            Some(exact) => Verification::FloatError(exact),
+            None => Verification::Approximate,
+        })
+    }
+}
+
+/// 部分式の値を計算する
+impl Visitor for Evaluator {
+    type Output = Evaluation;
+    type Error = EvaluationError;
+
+    fn arity(&self, token: Token) -> Option<Arity> {
+        if self.unary_ops.contains_key(&token) {
+            Some(Arity::Unary)
+        } else if self.binary_ops.contains_key(&token) {
+            Some(Arity::Binary)
+        } else {
+            None
+        }
+    }
+
+    fn number(&self, digits: &str, decimal: bool) -> Result<Evaluation, EvaluationError> {
+        // 小数は`Equation::from_decimal`と同じく値を決める
+        Ok(if decimal {
+            Evaluation {
+                value: format!("0.{}", digits).parse().unwrap(),
+                exact: digits
+                    .parse()
+                    .ok()
+                    .and_then(|n| Rational::new(n, 10i128.checked_pow(digits.len() as u32)?)),
+            }
+        } else {
+            Evaluation {
+                value: digits.parse().unwrap(),
+                exact: digits.parse().ok().map(Rational::integer),
+            }
+        })
+    }
+
+    fn unary(
+        &self,
+        position: usize,
+        token: Token,
+        arg: Evaluation,
+    ) -> Result<Evaluation, EvaluationError> {
+        let (name, op) = self
+            .unary_ops
+            .get(&token)
+            .ok_or(EvaluationError::UnknownToken(position, token))?;
+        let value = (op.apply)(arg.value)
+            .ok_or_else(|| EvaluationError::NotApplicable(position, name.clone()))?;
+        let exact = op.exact.as_ref().zip(arg.exact).and_then(|(f, x)| f(x));
+        Ok(Evaluation { value, exact })
+    }
+
+    fn binary(
+        &self,
+        position: usize,
+        token: Token,
+        arg1: Evaluation,
+        arg2: Evaluation,
+    ) -> Result<Evaluation, EvaluationError> {
+        let (name, op) = self
+            .binary_ops
+            .get(&token)
+            .ok_or(EvaluationError::UnknownToken(position, token))?;
+        let value = (op.apply)(arg1.value, arg2.value)
+            .ok_or_else(|| EvaluationError::NotApplicable(position, name.clone()))?;
+        let exact = match (&op.exact, arg1.exact, arg2.exact) {
+            (Some(f), Some(x), Some(y)) => f(x, y),
+            _ => None,
+        };
+        Ok(Evaluation { value, exact })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        registry::{catalogue, Registry},
+        search::DECIMAL_POINT,
+    };
+
+    fn equation(tokens: &[Token], value: Value) -> Equation {
+        Equation {
+            tokens: tokens.to_vec(),
+            cost: 0,
+            value,
+        }
+    }
+
+    fn evaluator() -> Evaluator {
+        let mut registry = Registry::standard();
+        registry.add_unary(catalogue::ln()).unwrap();
+        registry.add_unary(catalogue::exp()).unwrap();
+        registry.evaluator()
+    }
+
+    #[test]
+    fn exact_results() {
+        let evaluator = evaluator();
+        // 44/4+√4
+        let e = equation(&[0xe4, 0xf4, 0xe4, 0x13, 0xe4, 0x01, 0x10], 13f64);
+        let evaluation = evaluator.evaluate(&e.tokens).unwrap();
+        assert_eq!(evaluation.value, 13f64);
+        assert_eq!(evaluation.exact, Some(Rational::integer(13)));
+        assert_eq!(evaluator.verify(13, &e), Ok(Verification::Exact));
+        // √.4^4 = .16 は厳密には整数でない
+        let evaluation = evaluator
+            .evaluate(&[0xe4, DECIMAL_POINT, 0xe4, 0x14])
+            .unwrap();
+        assert_eq!(evaluation.exact, Rational::new(16, 625));
+    }
+
+    #[test]
+    fn approximate_results() {
+        let evaluator = evaluator();
+        // ln(exp(4))
+        let e = equation(&[0xe4, 0x2a, 0x28], 4f64);
+        assert_eq!(evaluator.evaluate(&e.tokens).unwrap().exact, None);
+        assert_eq!(evaluator.verify(4, &e), Ok(Verification::Approximate));
+        assert!(!Verification::Approximate.is_wrong());
+    }
+
+    #[test]
+    fn float_errors() {
+        let evaluator = evaluator();
+        // 4+4/4^44 は浮動小数点では4になる
+        let e = equation(&[0xe4, 0xe4, 0xe4, 0xe4, 0xf4, 0x14, 0x13, 0x10], 4f64);
+        let exact = Rational::new((1 << 88) + 1, 1 << 86).unwrap();
+        assert_eq!(evaluator.evaluate(&e.tokens).unwrap().value, 4f64);
+        assert_eq!(evaluator.verify(4, &e), Ok(Verification::FloatError(exact)));
+        assert!(Verification::FloatError(exact).is_wrong());
+    }
+
+    #[test]
+    fn mismatches() {
+        let evaluator = evaluator();
+        // 記録された値が計算し直した値と異なる
+        let e = equation(&[0xe4, 0xe4, 0x10], 9f64);
+        assert_eq!(evaluator.verify(8, &e), Ok(Verification::Mismatch(8f64)));
+        // 計算し直した値が整数と異なる
+        let e = equation(&[0xe4, 0xe4, 0x10], 8f64);
+        assert_eq!(evaluator.verify(9, &e), Ok(Verification::Mismatch(8f64)));
+        assert!(Verification::Mismatch(8f64).is_wrong());
+    }
+
+    #[test]
+    fn overflow_drops_exact_value() {
+        let max = Rational::integer(i128::MAX);
+        let one = Rational::integer(1);
+        assert_eq!(max.checked_add(&one), None);
+        assert_eq!(max.checked_neg().unwrap().checked_sub(&one), None);
+        assert_eq!(max.checked_sub(&Rational::integer(-1)), None);
+        assert_eq!(max.checked_mul(&Rational::integer(2)), None);
+        assert_eq!(
+            one.checked_div(&Rational::new(1, i128::MAX).unwrap()),
+            Some(max)
+        );
+        assert_eq!(
+            Rational::new(1, 2)
+                .unwrap()
+                .checked_div(&Rational::new(i128::MAX, 1).unwrap()),
+            None
+        );
+        assert_eq!(Rational::integer(4).checked_pow(64), None);
+        assert_eq!(Rational::integer(4).checked_pow(-64), None);
+        assert_eq!(
+            Rational::integer(4).checked_powr(&Rational::integer(256)),
+            None
+        );
+
+        // 4^4^4 は浮動小数点では求まるが、厳密な値は求めない
+        let evaluation = evaluator()
+            .evaluate(&[0xe4, 0xe4, 0xe4, 0x14, 0x14])
+            .unwrap();
+        assert_eq!(evaluation.value, 4f64.powi(256));
+        assert_eq!(evaluation.exact, None);
+        let e = equation(&[0xe4, 0xe4, 0xe4, 0x14, 0x14], 4f64.powi(256));
+        assert!(matches!(
+            evaluator().verify(4, &e),
+            Ok(Verification::Mismatch(_))
+        ));
+    }
+
+    #[test]
+    fn malformed_token_sequences() {
+        let evaluator = evaluator();
+        assert_eq!(evaluator.evaluate(&[]), Err(EvaluationError::Empty));
+        assert_eq!(
+            evaluator.evaluate(&[0xe4, 0x10]),
+            Err(EvaluationError::MissingOperand(1))
+        );
+        assert_eq!(
+            evaluator.evaluate(&[0xf4]),
+            Err(EvaluationError::MisplacedDigit(0))
+        );
+        assert_eq!(
+            evaluator.evaluate(&[0xe4, 0xe4, 0x10, 0xf4]),
+            Err(EvaluationError::MisplacedDigit(3))
+        );
+        // 小数点の付いた数値には桁も小数点も続かない
+        assert_eq!(
+            evaluator.evaluate(&[0xe4, DECIMAL_POINT, 0xf4]),
+            Err(EvaluationError::MisplacedDigit(2))
+        );
+        assert_eq!(
+            evaluator.evaluate(&[0xe4, 0xe4]),
+            Err(EvaluationError::LeftoverOperands(1))
+        );
+        assert_eq!(
+            evaluator.evaluate(&[0xe4, 0x7f]),
+            Err(EvaluationError::UnknownToken(1, 0x7f))
+        );
+        assert_eq!(
+            evaluator.evaluate(&[0xe4, 0x00, 0x01]),
+            Err(EvaluationError::NotApplicable(2, "sqrt".to_string()))
+        );
+    }
+}
@@ -0,0 +1,746 @@
+//! 文字列で定義するユーザー定義演算子
+//!
+//! ```text
+//! T(x) = x*(x+1)/2 where x >= 0 && is_int(x)
+//! a ⊕ b = a*b + a + b
+//! f(a, b) = (a + b) / 2
+//! ```
+//!
+//! `where`以降は定義域の条件で、条件を満たさない場合や結果が有限の値でない場合は適用できない。
+//! 式では`+ - * / % ^`、比較演算子、`&& || !`、数値定数、`pi`、`e`と以下の関数が使える。
+//! `sqrt`, `abs`, `floor`, `ceil`, `round`, `ln`, `log10`, `exp`, `min`, `max`, `is_int`
+
+use crate::{
+    registry::{BinaryOpDef, OpDef, UnaryOpDef},
+    search::{BinaryOp, Token, UnaryOp, Value},
+};
+use std::{error, fmt, str::FromStr, sync::Arc};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormulaError {
+    /// 定義の左辺（`T(x)`や`a ⊕ b`）として解釈できない
+    InvalidHeader(String),
+    /// 解釈できない文字
+    UnexpectedChar(usize, char),
+    /// 予期しない字句
+    UnexpectedToken(usize, String),
+    /// 式が途中で終わっている
+    UnexpectedEnd,
+    /// 引数として宣言されていない変数
+    UnknownVariable(String),
+    /// 存在しない関数
+    UnknownFunction(String),
+    /// 関数の引数の数が違う
+    WrongArity {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for FormulaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormulaError::InvalidHeader(header) => write!(
+                f,
+                "`{}` is not a valid definition header (expected `f(x)`, `f(a, b)` or `a ⊕ b`)",
+                header
+            ),
+            FormulaError::UnexpectedChar(position, c) => {
+                write!(f, "Unexpected character `{}` at {}", c, position)
+            }
+            FormulaError::UnexpectedToken(position, token) => {
+                write!(f, "Unexpected `{}` at {}", token, position)
+            }
+            FormulaError::UnexpectedEnd => write!(f, "Unexpected end of formula"),
+            FormulaError::UnknownVariable(name) => write!(f, "Unknown variable `{}`", name),
+            FormulaError::UnknownFunction(name) => write!(f, "Unknown function `{}`", name),
+            FormulaError::WrongArity {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Function `{}` takes {} argument(s) but {} given",
+                name, expected, found
+            ),
+        }
+    }
+}
+
+impl error::Error for FormulaError {}
+
+/// 演算子の表記
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notation {
+    /// 関数呼び出し（`T(x)`, `f(a, b)`）
+    Call,
+    /// 中置記号（`a ⊕ b`）。英字の記号は前後に空白を入れて表示する（`a mod b`）
+    Infix(String),
+}
+
+/// 構文木
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(Value),
+    /// 引数の番号
+    Variable(usize),
+    Negate(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Function {
+    Sqrt,
+    Abs,
+    Floor,
+    Ceil,
+    Round,
+    Ln,
+    Log10,
+    Exp,
+    Min,
+    Max,
+    IsInt,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<(Function, usize)> {
+        Some(match name {
+            "sqrt" => (Function::Sqrt, 1),
+            "abs" => (Function::Abs, 1),
+            "floor" => (Function::Floor, 1),
+            "ceil" => (Function::Ceil, 1),
+            "round" => (Function::Round, 1),
+            "ln" => (Function::Ln, 1),
+            "log10" => (Function::Log10, 1),
+            "exp" => (Function::Exp, 1),
+            "min" => (Function::Min, 2),
+            "max" => (Function::Max, 2),
+            "is_int" => (Function::IsInt, 1),
+            _ => return None,
+        })
+    }
+}
+
+fn truth(b: bool) -> Value {
+    if b {
+        1f64
+    } else {
+        0f64
+    }
+}
+
+impl Expr {
+    fn eval(&self, args: &[Value]) -> Value {
+        match self {
+            Expr::Number(v) => *v,
+            Expr::Variable(i) => args[*i],
+            Expr::Negate(e) => -e.eval(args),
+            Expr::Not(e) => truth(e.eval(args) == 0f64),
+            Expr::Binary(op, lhs, rhs) => {
+                let l = lhs.eval(args);
+                // 論理演算は短絡評価する
+                match op {
+                    BinOp::And if l == 0f64 => return 0f64,
+                    BinOp::Or if l != 0f64 => return 1f64,
+                    _ => {}
+                }
+                let r = rhs.eval(args);
+                match op {
+                    BinOp::Add => l + r,
+                    BinOp::Sub => l - r,
+                    BinOp::Mul => l * r,
+                    BinOp::Div => l / r,
+                    BinOp::Rem => l % r,
+                    BinOp::Pow => l.powf(r),
+                    BinOp::Lt => truth(l < r),
+                    BinOp::Le => truth(l <= r),
+                    BinOp::Gt => truth(l > r),
+                    BinOp::Ge => truth(l >= r),
+                    BinOp::Eq => truth(l == r),
+                    BinOp::Ne => truth(l != r),
+                    BinOp::And | BinOp::Or => truth(r != 0f64),
+                }
+            }
+            Expr::Call(f, a) => {
+                let x = a[0].eval(args);
+                match f {
+                    Function::Sqrt => x.sqrt(),
+                    Function::Abs => x.abs(),
+                    Function::Floor => x.floor(),
+                    Function::Ceil => x.ceil(),
+                    Function::Round => x.round(),
+                    Function::Ln => x.ln(),
+                    Function::Log10 => x.log10(),
+                    Function::Exp => x.exp(),
+                    Function::Min => x.min(a[1].eval(args)),
+                    Function::Max => x.max(a[1].eval(args)),
+                    // 誤差で整数をわずかに下回る値（`2.9999999999999996`）も整数とみなす
+                    Function::IsInt => truth(x.is_finite() && (x - x.round()).abs() < 1e-9),
+                }
+            }
+        }
+    }
+}
+
+/// 字句
+#[derive(Debug, Clone, PartialEq)]
+enum Lexeme {
+    Number(Value),
+    Ident(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Lexeme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lexeme::Number(v) => write!(f, "{}", v),
+            Lexeme::Ident(name) => write!(f, "{}", name),
+            Lexeme::Symbol(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+const SYMBOLS: [&str; 19] = [
+    "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "^", "(", ")", ",", "<", ">", "!",
+    "=",
+];
+
+/// `offset`は`text`の定義全体の中での開始位置（エラー表示用）
+fn lex(text: &str, offset: usize) -> Result<Vec<(usize, Lexeme)>, FormulaError> {
+    let mut lexemes = vec![];
+    let mut rest = text;
+    let mut position = offset;
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_whitespace() {
+            c.len_utf8()
+        } else if c.is_ascii_digit() || c == '.' {
+            let len = rest
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .unwrap_or(rest.len());
+            let number = rest[..len]
+                .parse()
+                .map_err(|_| FormulaError::UnexpectedToken(position, rest[..len].to_string()))?;
+            lexemes.push((position, Lexeme::Number(number)));
+            len
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            lexemes.push((position, Lexeme::Ident(rest[..len].to_string())));
+            len
+        } else if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            lexemes.push((position, Lexeme::Symbol(symbol)));
+            symbol.len()
+        } else {
+            return Err(FormulaError::UnexpectedChar(position, c));
+        };
+        rest = &rest[len..];
+        position += len;
+    }
+    Ok(lexemes)
+}
+
+/// 再帰下降構文解析
+struct Parser<'a> {
+    lexemes: Vec<(usize, Lexeme)>,
+    index: usize,
+    params: &'a [String],
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Lexeme> {
+        self.lexemes.get(self.index).map(|(_, l)| l)
+    }
+
+    fn next(&mut self) -> Result<Lexeme, FormulaError> {
+        let lexeme = self
+            .lexemes
+            .get(self.index)
+            .map(|(_, l)| l.clone())
+            .ok_or(FormulaError::UnexpectedEnd)?;
+        self.index += 1;
+        Ok(lexeme)
+    }
+
+    fn unexpected(&self) -> FormulaError {
+        match self.lexemes.get(self.index) {
+            Some((position, lexeme)) => {
+                FormulaError::UnexpectedToken(*position, lexeme.to_string())
+            }
+            None => FormulaError::UnexpectedEnd,
+        }
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        if self.peek() == Some(&Lexeme::Symbol(symbol_ref(symbol))) {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), FormulaError> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn parse_all(mut self) -> Result<Expr, FormulaError> {
+        let expr = self.or()?;
+        if self.index < self.lexemes.len() {
+            return Err(self.unexpected());
+        }
+        Ok(expr)
+    }
+
+    /// 左結合の二項演算子の並びを読む
+    fn binary_chain(
+        &mut self,
+        ops: &[(&str, BinOp)],
+        operand: fn(&mut Self) -> Result<Expr, FormulaError>,
+    ) -> Result<Expr, FormulaError> {
+        let mut lhs = operand(self)?;
+        'outer: loop {
+            for (symbol, op) in ops {
+                if self.eat(symbol) {
+                    let rhs = operand(self)?;
+                    lhs = Expr::Binary(*op, Box::new(lhs), Box::new(rhs));
+                    continue 'outer;
+                }
+            }
+            return Ok(lhs);
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, FormulaError> {
+        self.binary_chain(&[("||", BinOp::Or)], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Expr, FormulaError> {
+        self.binary_chain(&[("&&", BinOp::And)], Self::comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, FormulaError> {
+        self.binary_chain(
+            &[
+                ("<=", BinOp::Le),
+                (">=", BinOp::Ge),
+                ("==", BinOp::Eq),
+                ("!=", BinOp::Ne),
+                ("<", BinOp::Lt),
+                (">", BinOp::Gt),
+            ],
+            Self::sum,
+        )
+    }
+
+    fn sum(&mut self) -> Result<Expr, FormulaError> {
+        self.binary_chain(&[("+", BinOp::Add), ("-", BinOp::Sub)], Self::term)
+    }
+
+    fn term(&mut self) -> Result<Expr, FormulaError> {
+        self.binary_chain(
+            &[("*", BinOp::Mul), ("/", BinOp::Div), ("%", BinOp::Rem)],
+            Self::unary,
+        )
+    }
+
+    fn unary(&mut self) -> Result<Expr, FormulaError> {
+        if self.eat("-") {
+            Ok(Expr::Negate(Box::new(self.unary()?)))
+        } else if self.eat("!") {
+            Ok(Expr::Not(Box::new(self.unary()?)))
+        } else {
+            self.power()
+        }
+    }
+
+    /// 累乗は右結合
+    fn power(&mut self) -> Result<Expr, FormulaError> {
+        let base = self.atom()?;
+        if self.eat("^") {
+            let exponent = self.unary()?;
+            return Ok(Expr::Binary(BinOp::Pow, Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Expr, FormulaError> {
+        let index = self.index;
+        match self.next()? {
+            Lexeme::Number(v) => Ok(Expr::Number(v)),
+            Lexeme::Symbol("(") => {
+                let expr = self.or()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Lexeme::Ident(name) if self.eat("(") => {
+                let mut args = vec![self.or()?];
+                while self.eat(",") {
+                    args.push(self.or()?);
+                }
+                self.expect(")")?;
+                let (function, arity) = Function::from_name(&name)
+                    .ok_or_else(|| FormulaError::UnknownFunction(name.clone()))?;
+                if args.len() != arity {
+                    return Err(FormulaError::WrongArity {
+                        name,
+                        expected: arity,
+                        found: args.len(),
+                    });
+                }
+                Ok(Expr::Call(function, args))
+            }
+            Lexeme::Ident(name) => {
+                if let Some(i) = self.params.iter().position(|p| *p == name) {
+                    Ok(Expr::Variable(i))
+                } else {
+                    match name.as_str() {
+                        "pi" => Ok(Expr::Number(std::f64::consts::PI)),
+                        "e" => Ok(Expr::Number(std::f64::consts::E)),
+                        _ => Err(FormulaError::UnknownVariable(name)),
+                    }
+                }
+            }
+            _ => {
+                self.index = index;
+                Err(self.unexpected())
+            }
+        }
+    }
+}
+
+/// `SYMBOLS`の中の同じ記号への参照（`Lexeme::Symbol`は`'static`な文字列を持つため）
+fn symbol_ref(symbol: &str) -> &'static str {
+    SYMBOLS.iter().find(|s| **s == symbol).unwrap()
+}
+
+/// 文字列から作られた演算子の定義
+#[derive(Debug, Clone)]
+pub struct Formula {
+    /// 関数名または中置記号
+    pub name: String,
+    /// 引数名
+    pub params: Vec<String>,
+    pub notation: Notation,
+    body: Expr,
+    /// 定義域の条件
+    guard: Option<Expr>,
+}
+
+impl Formula {
+    /// 中置記号の演算子の優先度。乗除より強く、累乗より弱く結合する。
+    /// 結合性は指定しないので、同じ演算子を連ねると括弧を付ける。
+    /// ルールファイルでは`precedence`, `left_associative`, `right_associative`で変更できる
+    pub const INFIX_PRECEDENCE: i32 = 4;
+
+    pub fn parse(text: &str) -> Result<Formula, FormulaError> {
+        let (header, definition) = text
+            .split_once('=')
+            .ok_or_else(|| FormulaError::InvalidHeader(text.trim().to_string()))?;
+        let (name, params, notation) = parse_header(header.trim())?;
+        let offset = header.len() + 1;
+        let (body, guard) = match find_keyword(definition, "where") {
+            Some(i) => (
+                &definition[..i],
+                Some((&definition[i + 5..], offset + i + 5)),
+            ),
+            None => (definition, None),
+        };
+        let parser = |text: &str, offset: usize| {
+            Ok::<_, FormulaError>(Parser {
+                lexemes: lex(text, offset)?,
+                index: 0,
+                params: &params,
+            })
+        };
+        let body = parser(body, offset)?.parse_all()?;
+        let guard = match guard {
+            Some((text, offset)) => Some(parser(text, offset)?.parse_all()?),
+            None => None,
+        };
+        Ok(Formula {
+            name,
+            params,
+            notation,
+            body,
+            guard,
+        })
+    }
+
+    /// 引数に適用する。定義域外や結果が有限でない場合は`None`
+    pub fn eval(&self, args: &[Value]) -> Option<Value> {
+        if let Some(guard) = &self.guard {
+            if guard.eval(args) == 0f64 {
+                return None;
+            }
+        }
+        let value = self.body.eval(args);
+        value.is_finite().then_some(value)
+    }
+
+    /// 演算子定義に変換する。表示は関数呼び出し（`T(x)`）または中置記号（`a ⊕ b`）の形になる
+    pub fn into_def(self, name: impl Into<String>, token: Token, cost: u8) -> OpDef {
+        let formula = Arc::new(self);
+        let f = formula.clone();
+        match (formula.params.len(), &formula.notation) {
+            (1, _) => UnaryOpDef::new(
+                name,
+                UnaryOp::new(token, cost, move |x| f.eval(&[x])),
+                format!("{}(", formula.name),
+                ")",
+                0,
+                false,
+            )
+            .into(),
+            (_, Notation::Call) => BinaryOpDef::new(
+                name,
+                BinaryOp::new(token, cost, move |a, b| f.eval(&[a, b])),
+                format!("{}(", formula.name),
+                ", ",
+                ")",
+                0,
+                false,
+                false,
+                false,
+                false,
+            )
+            .into(),
+            (_, Notation::Infix(symbol)) => BinaryOpDef::new(
+                name,
+                BinaryOp::new(token, cost, move |a, b| f.eval(&[a, b])),
+                "",
+                symbol.clone(),
+                "",
+                Self::INFIX_PRECEDENCE,
+                false,
+                false,
+                true,
+                true,
+            )
+            .into(),
+        }
+    }
+}
+
+impl FromStr for Formula {
+    type Err = FormulaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Formula::parse(s)
+    }
+}
+
+fn is_ident(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// `f(x)`, `f(a, b)`, `a ⊕ b`のいずれかを読む
+fn parse_header(header: &str) -> Result<(String, Vec<String>, Notation), FormulaError> {
+    let invalid = || FormulaError::InvalidHeader(header.to_string());
+    if let Some(args) = header.strip_suffix(')') {
+        let (name, args) = args.split_once('(').ok_or_else(invalid)?;
+        let name = name.trim();
+        let params = args
+            .split(',')
+            .map(|p| p.trim().to_string())
+            .collect::<Vec<_>>();
+        if !is_ident(name) || params.len() > 2 || !params.iter().all(|p| is_ident(p)) {
+            return Err(invalid());
+        }
+        return Ok((name.to_string(), params, Notation::Call));
+    }
+    // 中置記号
+    let lhs_len = header
+        .find(|c: char| !c.is_alphanumeric() && c != '_')
+        .ok_or_else(invalid)?;
+    let rhs_start = header
+        .rfind(|c: char| !c.is_alphanumeric() && c != '_')
+        .ok_or_else(invalid)?
+        + 1;
+    let (a, b) = (&header[..lhs_len], &header[rhs_start..]);
+    let symbol = header[lhs_len..rhs_start].trim();
+    if !is_ident(a) || !is_ident(b) || a == b || symbol.is_empty() || symbol.contains(' ') {
+        return Err(invalid());
+    }
+    // 英字の記号は、項の数字と続けて表示すると読めない（`4mod4`）
+    let infix = if is_ident(symbol) {
+        format!(" {} ", symbol)
+    } else {
+        symbol.to_string()
+    };
+    Ok((
+        symbol.to_string(),
+        vec![a.to_string(), b.to_string()],
+        Notation::Infix(infix),
+    ))
+}
+
+/// 識別子の一部ではない`keyword`の位置
+fn find_keyword(text: &str, keyword: &str) -> Option<usize> {
+    let is_ident_char = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices(keyword).map(|(i, _)| i).find(|&i| {
+        !text[..i].ends_with(is_ident_char) && !text[i + keyword.len()..].starts_with(is_ident_char)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        registry::Registry,
+        search::{Equation, DECIMAL_POINT},
+    };
+
+    fn eval(text: &str, args: &[Value]) -> Option<Value> {
+        Formula::parse(text).unwrap().eval(args)
+    }
+
+    /// 式で定義した演算子だけを登録して、`tokens`を表示する
+    fn print(text: &str, tokens: &[Token]) -> String {
+        let mut registry = Registry::new();
+        let def =
+            Formula::parse(text)
+                .unwrap()
+                .into_def("f", registry.next_user_token().unwrap(), 1);
+        registry.add(def).unwrap();
+        let e = Equation {
+            tokens: tokens.to_vec(),
+            cost: 0,
+            value: 0f64,
+        };
+        registry.printer().print(&e).unwrap()
+    }
+
+    #[test]
+    fn operators_follow_precedence() {
+        assert_eq!(eval("f(a, b) = a + b * 2 ^ 2", &[1f64, 1f64]), Some(5f64));
+        assert_eq!(eval("f(a, b) = (a + b) * 2", &[1f64, 1f64]), Some(4f64));
+        // 減算と除算は左結合、累乗は右結合
+        assert_eq!(eval("f(a, b) = a - b - 1", &[4f64, 1f64]), Some(2f64));
+        assert_eq!(eval("f(a, b) = a / b / 2", &[8f64, 2f64]), Some(2f64));
+        assert_eq!(eval("f(a, b) = a ^ b ^ 2", &[2f64, 3f64]), Some(512f64));
+        // 比較は算術より弱く、`&&`は`||`より強く結合する
+        assert_eq!(eval("f(a, b) = a + 1 < b", &[1f64, 3f64]), Some(1f64));
+        assert_eq!(
+            eval("f(a, b) = a == 1 || a == 2 && b == 0", &[1f64, 1f64]),
+            Some(1f64)
+        );
+        assert_eq!(
+            eval("f(a, b) = min(a, b) + max(a, b) % 3", &[1f64, 5f64]),
+            Some(3f64)
+        );
+    }
+
+    #[test]
+    fn unary_minus_binds_looser_than_power() {
+        // -x^2は-(x^2)
+        assert_eq!(eval("f(x) = -x^2", &[3f64]), Some(-9f64));
+        assert_eq!(eval("f(x) = (-x)^2", &[3f64]), Some(9f64));
+        assert_eq!(eval("f(x) = 2^-x", &[1f64]), Some(0.5));
+        assert_eq!(eval("f(x) = --x", &[3f64]), Some(3f64));
+        assert_eq!(eval("f(x) = !x", &[0f64]), Some(1f64));
+    }
+
+    #[test]
+    fn guards_restrict_the_domain() {
+        let t = "T(x) = x*(x+1)/2 where x >= 0 && is_int(x)";
+        assert_eq!(eval(t, &[3f64]), Some(6f64));
+        assert_eq!(eval(t, &[-1f64]), None);
+        assert_eq!(eval(t, &[2.5]), None);
+        // 誤差で整数をわずかに下回る値も整数とみなす
+        assert!(eval(t, &[3f64 - 1e-12]).is_some());
+        assert!(eval(t, &[3f64 + 1e-12]).is_some());
+        // 条件の中の識別子`where`は区切りにならない
+        assert_eq!(eval("f(where_) = where_ + 1", &[1f64]), Some(2f64));
+    }
+
+    #[test]
+    fn non_finite_results_are_not_applicable() {
+        assert_eq!(eval("f(a, b) = a / b", &[1f64, 0f64]), None);
+        assert_eq!(eval("f(a, b) = a / b", &[0f64, 0f64]), None);
+        assert_eq!(eval("f(a, b) = a % b", &[1f64, 0f64]), None);
+        assert_eq!(eval("f(x) = ln(x)", &[0f64]), None);
+        assert_eq!(eval("f(x) = sqrt(x)", &[-1f64]), None);
+    }
+
+    #[test]
+    fn header_errors() {
+        let error = |text: &str| Formula::parse(text).unwrap_err();
+        let invalid = |header: &str| FormulaError::InvalidHeader(header.to_string());
+        assert_eq!(error("f(x)"), invalid("f(x)"));
+        assert_eq!(error("f(a, b, c) = a"), invalid("f(a, b, c)"));
+        assert_eq!(error("f(1) = 1"), invalid("f(1)"));
+        assert_eq!(error("a + a = a"), invalid("a + a"));
+        assert_eq!(error("a m o d b = a"), invalid("a m o d b"));
+        assert_eq!(error("ab = a"), invalid("ab"));
+        assert_eq!(
+            error("f(x) = y"),
+            FormulaError::UnknownVariable("y".to_string())
+        );
+        assert_eq!(
+            error("f(x) = g(x)"),
+            FormulaError::UnknownFunction("g".to_string())
+        );
+        assert_eq!(
+            error("f(x) = min(x)"),
+            FormulaError::WrongArity {
+                name: "min".to_string(),
+                expected: 2,
+                found: 1
+            }
+        );
+        assert_eq!(error("f(x) = x $ 1"), FormulaError::UnexpectedChar(9, '$'));
+        assert_eq!(
+            error("f(x) = x 1"),
+            FormulaError::UnexpectedToken(9, "1".to_string())
+        );
+        assert_eq!(error("f(x) = x +"), FormulaError::UnexpectedEnd);
+    }
+
+    #[test]
+    fn infix_and_call_printing() {
+        let a = 0x80;
+        assert_eq!(
+            print("a mod b = a - b*floor(a/b)", &[0xe4, 0xe4, a]),
+            "4 mod 4"
+        );
+        assert_eq!(print("a ⊕ b = a*b + a + b", &[0xe4, 0xe4, a]), "4⊕4");
+        // 結合性を指定しないので、同じ演算子を連ねると括弧を付ける
+        assert_eq!(
+            print("a ⊕ b = a*b + a + b", &[0xe4, 0xe4, a, 0xe4, a]),
+            "(4⊕4)⊕4"
+        );
+        assert_eq!(
+            print("f(a, b) = (a + b) / 2", &[0xe4, 0xe4, DECIMAL_POINT, a]),
+            "f(4, .4)"
+        );
+        assert_eq!(print("T(x) = x*(x+1)/2", &[0xe4, a]), "T(4)");
+    }
+}
@@ -1,13 +1,21 @@
 #[cfg(target_arch = "wasm32")]
 extern crate wasm_bindgen;
 
+pub mod ast;
+pub mod evaluate;
+pub mod formula;
+pub mod parse;
+pub mod postfix;
+pub mod preset;
 pub mod print;
+pub mod registry;
+pub mod rules;
 pub mod search;
 #[cfg(target_arch = "wasm32")]
 pub mod wasm;
 
 pub use print::*;
-use search::{BinaryOp, Equation, Knowledge, Searcher, UnaryOp};
+use search::{BinaryOp, Equation, Knowledge, SearchStats, Searcher, UnaryOp, Value};
 use std::collections::{hash_map::Entry, HashMap};
 #[cfg(all(target_arch = "wasm32", feature = "with-rayon"))]
 pub use wasm_bindgen_rayon::init_thread_pool;
@@ -17,14 +25,49 @@ pub fn search_int(
     b_ops: Vec<BinaryOp>,
     numbers: &str,
 ) -> HashMap<i32, Equation> {
-    // 探索
-    let searcher = Searcher::new(u_ops, b_ops);
-    let mut memo = HashMap::<String, Knowledge>::default();
-    searcher.search(&mut memo, numbers);
-    let knowledge = &memo.get(numbers).unwrap();
+    search_int_with_stats(u_ops, b_ops, numbers).0
+}
+
+/// `search_int`と同様に探索し、探索の統計もあわせて返す
+pub fn search_int_with_stats(
+    u_ops: Vec<UnaryOp>,
+    b_ops: Vec<BinaryOp>,
+    numbers: &str,
+) -> (HashMap<i32, Equation>, SearchStats) {
+    search_int_by(&Searcher::new(u_ops, b_ops), numbers)
+}
 
-    // 整数値のみを出力する
+/// 与えられた`Searcher`で探索し、整数値となる式と探索の統計を返す
+pub fn search_int_by(searcher: &Searcher, numbers: &str) -> (HashMap<i32, Equation>, SearchStats) {
+    search_int_among(searcher, &[numbers.to_string()])
+}
+
+/// `search_int_by`と同様だが、数字を並べ替えたすべての列を探索する（24ゲームなど）
+pub fn search_int_permuted(
+    searcher: &Searcher,
+    numbers: &str,
+) -> (HashMap<i32, Equation>, SearchStats) {
+    search_int_among(searcher, &permutations(numbers))
+}
+
+/// 複数の数値列を探索し、それぞれの整数値について最もコストの低い式を返す
+fn search_int_among(
+    searcher: &Searcher,
+    numbers_list: &[String],
+) -> (HashMap<i32, Equation>, SearchStats) {
+    // 探索（部分列の結果は数値列をまたいで再利用する）
+    let mut memo = HashMap::<String, Knowledge>::default();
+    let mut stats = SearchStats::default();
     let mut results = HashMap::<i32, Equation>::new();
+    for numbers in numbers_list {
+        searcher.search_with_stats(&mut memo, numbers, &mut stats);
+        collect_int(&memo[numbers], &mut results);
+    }
+    (results, stats)
+}
+
+/// 整数値のみを取り出す
+fn collect_int(knowledge: &Knowledge, results: &mut HashMap<i32, Equation>) {
     for r in knowledge.iter() {
         cfg_if::cfg_if! {
             if #[cfg(feature = "with-rayon")] {
@@ -33,9 +76,8 @@ pub fn search_int(
                 let (_, e) = r;
             }
         }
-        if e.value >= 0f64 && e.value < 2000f64 && e.value.fract().abs() < 1e-9 {
-            let rounded = e.value.round() as i32;
-            match results.entry(rounded) {
+        if let Some(n) = nearest_int(e.value).filter(|n| (0..2000).contains(n)) {
+            match results.entry(n) {
                 Entry::Occupied(mut o) => {
                     if o.get().cost > e.cost {
                         o.insert(e.clone());
@@ -47,5 +89,51 @@ pub fn search_int(
             }
         }
     }
+}
+
+/// 整数とみなせる値なら、最も近い整数を返す。
+/// 誤差で整数をわずかに下回る値（`8/(3-8/3)`は`23.99999999999999`）も整数とみなす
+fn nearest_int(v: Value) -> Option<i32> {
+    let rounded = v.round();
+    ((v - rounded).abs() < 1e-9 && rounded.abs() < i32::MAX as Value).then_some(rounded as i32)
+}
+
+/// 数字の重複のない並べ替えをすべて列挙する
+fn permutations(numbers: &str) -> Vec<String> {
+    let mut digits = numbers.chars().collect::<Vec<_>>();
+    digits.sort_unstable();
+    let mut results = vec![];
+    loop {
+        results.push(digits.iter().collect());
+        // 辞書順で次の並びに進める
+        let Some(i) = (1..digits.len()).rev().find(|&i| digits[i - 1] < digits[i]) else {
+            break;
+        };
+        let j = (i..digits.len())
+            .rev()
+            .find(|&j| digits[i - 1] < digits[j])
+            .unwrap();
+        digits.swap(i - 1, j);
+        digits[i..].reverse();
+    }
     results
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_just_below_an_integer_are_integers() {
+        // 24ゲームの 3388
+        assert_eq!(nearest_int(8f64 / (3f64 - 8f64 / 3f64)), Some(24));
+        // テンパズルの 11189
+        assert_eq!(nearest_int(1f64 + 1f64 / (1f64 - 8f64 / 9f64)), Some(10));
+        assert_eq!(nearest_int(2f64 / 7f64 * 5f64 * 7f64), Some(10));
+        assert_eq!(nearest_int(0.1f64 + 0.2f64 - 0.3f64), Some(0));
+        assert_eq!(nearest_int(-4f64), Some(-4));
+        assert_eq!(nearest_int(23.5f64), None);
+        assert_eq!(nearest_int(23.999f64), None);
+        assert_eq!(nearest_int(Value::NAN), None);
+    }
+}
@@ -0,0 +1,649 @@
+//! `Printer`と同じ表示方法で書かれた式（`(4+4)/4-√4`など）を読み、`Equation`に戻す
+//!
+//! 演算子の記号や優先度、結合性は`Printer`の定義をそのまま使うので、`Printer::print`の結果を読み戻せる。
+//! 結合的な演算子の並び（`4+4+4`）は左から順に適用したものとして読む。空白は無視する
+
+use crate::{
+    print::{BinaryOpPrinter, Printer, Script, UnaryOpPrinter},
+    search::{BinaryOp, Equation, Token, UnaryOp},
+};
+use std::{collections::HashMap, error, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// 解釈できない文字
+    UnexpectedChar(usize, char),
+    /// 式が途中で終わっている
+    UnexpectedEnd,
+    /// 扱えない数値（桁が多すぎるなど）
+    InvalidNumber(usize, String),
+    /// 演算子を項に適用できない（`√(-4)`など）
+    NotApplicable(String),
+}
+
+impl ParseError {
+    /// 複数の読み方を試して全て失敗した場合に、最も先まで読めたものを選ぶための位置
+    fn position(&self) -> usize {
+        match self {
+            ParseError::UnexpectedChar(position, _) | ParseError::InvalidNumber(position, _) => {
+                *position
+            }
+            ParseError::UnexpectedEnd | ParseError::NotApplicable(_) => usize::MAX,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedChar(position, c) => {
+                write!(f, "Unexpected character `{}` at {}", c, position)
+            }
+            ParseError::UnexpectedEnd => write!(f, "Unexpected end of expression"),
+            ParseError::InvalidNumber(position, number) => {
+                write!(f, "Number `{}` at {} is out of range", number, position)
+            }
+            ParseError::NotApplicable(name) => {
+                write!(f, "Operator `{}` cannot be applied to its operand", name)
+            }
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
+/// 読み取った部分式と、その最後に適用した演算子の優先度の組
+type Parsed = (Equation, i32);
+
+/// 式を読む。値とコストは演算子を適用し直して求める
+#[derive(Debug, Clone)]
+pub struct Parser {
+    printer: Printer,
+    unary_ops: HashMap<Token, (String, UnaryOp)>,
+    binary_ops: HashMap<Token, (String, BinaryOp)>,
+}
+
+impl Parser {
+    pub fn new(
+        printer: Printer,
+        unary_ops: HashMap<Token, (String, UnaryOp)>,
+        binary_ops: HashMap<Token, (String, BinaryOp)>,
+    ) -> Parser {
+        Parser {
+            printer,
+            unary_ops,
+            binary_ops,
+        }
+    }
+
+    pub fn parse(&self, text: &str) -> Result<Equation, ParseError> {
+        Cursor::new(self, text, 0).parse_all()
+    }
+}
+
+/// 優先度が`precedence`の演算子の項として、括弧なしで書ける部分式の優先度の上限。
+/// 上限は優先度を2倍した値で表し、`inclusive`なら同じ優先度の演算子も含める
+fn bound(precedence: i32, inclusive: bool) -> i32 {
+    precedence.saturating_mul(2) - if inclusive { 0 } else { 1 }
+}
+
+/// `bound`で表した上限の範囲内の優先度かどうか
+fn within(precedence: i32, limit: i32) -> bool {
+    precedence.saturating_mul(2) <= limit
+}
+
+/// 演算子の前から始まる読み方の候補
+enum Prefixed<'a> {
+    Unary(&'a UnaryOpPrinter),
+    Binary(&'a BinaryOpPrinter),
+}
+
+/// 再帰下降構文解析。演算子の優先度は`bound`の上限で扱う
+struct Cursor<'a> {
+    parser: &'a Parser,
+    text: &'a str,
+    position: usize,
+    /// `text`の式全体の中での開始位置（上付き文字などの中を読む場合のエラー表示用）
+    offset: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(parser: &'a Parser, text: &'a str, offset: usize) -> Cursor<'a> {
+        Cursor {
+            parser,
+            text,
+            position: 0,
+            offset,
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn unexpected(&self) -> ParseError {
+        match self.rest().chars().next() {
+            Some(c) => ParseError::UnexpectedChar(self.offset + self.position, c),
+            None => ParseError::UnexpectedEnd,
+        }
+    }
+
+    /// 記号があれば読み進める。記号の前後の空白は無視する
+    fn eat(&mut self, symbol: &str) -> bool {
+        self.skip_whitespace();
+        match matched(self.rest(), symbol.trim()) {
+            Some(len) => {
+                self.position += len;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), ParseError> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn parse_all(mut self) -> Result<Equation, ParseError> {
+        let (equation, _) = self.expression(i32::MAX)?;
+        self.skip_whitespace();
+        if !self.rest().is_empty() {
+            return Err(self.unexpected());
+        }
+        Ok(equation)
+    }
+
+    /// 上付き文字などを通常の文字に戻した`text`を、独立した式として読む
+    fn nested(&self, text: &str, start: usize) -> Result<Parsed, ParseError> {
+        let equation = Cursor::new(self.parser, text, self.offset + start).parse_all()?;
+        Ok((equation, 0))
+    }
+
+    /// 優先度が`limit`までの演算子からなる式を読む
+    fn expression(&mut self, limit: i32) -> Result<Parsed, ParseError> {
+        let mut lhs = self.operand()?;
+        while let Some(expr) = self.postfix(&lhs, limit)? {
+            lhs = expr;
+        }
+        Ok(lhs)
+    }
+
+    /// 式の先頭になるもの（括弧、数値、記号で始まる演算子）を読む
+    fn operand(&mut self) -> Result<Parsed, ParseError> {
+        self.skip_whitespace();
+        let (paren_left, paren_right) = self.parser.printer.parens();
+        if self.eat(paren_left) {
+            let (equation, _) = self.expression(i32::MAX)?;
+            self.expect(paren_right)?;
+            return Ok((equation, 0));
+        }
+        if self
+            .rest()
+            .starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        {
+            return self.number();
+        }
+
+        // 記号の長いものから順に試し、読めたものを使う
+        let printer = &self.parser.printer;
+        let mut candidates = printer
+            .unary_ops()
+            .iter()
+            .filter(|op| !op.prefix.trim().is_empty())
+            .map(|op| (op.prefix.trim(), Prefixed::Unary(op)))
+            .chain(
+                printer
+                    .binary_ops()
+                    .iter()
+                    .filter(|op| !op.prefix.trim().is_empty() || is_raised(&op.left_script))
+                    .map(|op| (op.prefix.trim(), Prefixed::Binary(op))),
+            )
+            .filter(|(prefix, _)| matched(self.rest(), prefix).is_some())
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+
+        let start = self.position;
+        let mut error = None::<ParseError>;
+        for (_, candidate) in candidates {
+            let result = match candidate {
+                Prefixed::Unary(op) => self.prefixed_unary(op),
+                Prefixed::Binary(op) => self.prefixed_binary(op),
+            };
+            match result {
+                Ok(expr) => return Ok(expr),
+                Err(e) => {
+                    error = match error {
+                        Some(prev) if prev.position() >= e.position() => Some(prev),
+                        _ => Some(e),
+                    };
+                    self.position = start;
+                }
+            }
+        }
+        Err(error.unwrap_or_else(|| self.unexpected()))
+    }
+
+    /// 数値（`44`や`.4`）を読む
+    fn number(&mut self) -> Result<Parsed, ParseError> {
+        let start = self.position;
+        let decimal = self.eat(".");
+        let digits = self
+            .rest()
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(self.rest(), |len| &self.rest()[..len]);
+        if digits.is_empty() {
+            return Err(self.unexpected());
+        }
+        self.position += digits.len();
+        // `Equation`は`i32`で値を求めるので、それに収まる桁数に限る
+        if digits.len() > 9 {
+            return Err(ParseError::InvalidNumber(
+                self.offset + start,
+                self.text[start..self.position].to_string(),
+            ));
+        }
+        let equation = if decimal {
+            Equation::from_decimal(digits)
+        } else {
+            Equation::from_numbers(digits)
+        };
+        Ok((equation, 0))
+    }
+
+    /// 項の表示方法に従って項を読む
+    fn scripted(&mut self, script: &Script, limit: i32) -> Result<Parsed, ParseError> {
+        self.skip_whitespace();
+        let start = self.position;
+        match script {
+            Script::Normal => self.expression(limit),
+            Script::Overline => match self.overlined() {
+                Some(text) => self.nested(&text, start),
+                None => self.expression(limit),
+            },
+            Script::Subscript(fallback) | Script::Superscript(fallback) => {
+                let restored = self
+                    .rest()
+                    .chars()
+                    .map_while(|c| script.restore(c).map(|r| (c, r)))
+                    .collect::<Vec<_>>();
+                if !restored.is_empty() {
+                    self.position += restored.iter().map(|(c, _)| c.len_utf8()).sum::<usize>();
+                    let text = restored.iter().map(|(_, r)| r).collect::<String>();
+                    self.nested(&text, start)
+                } else if !fallback.is_empty() && self.eat(fallback) {
+                    // 変換できない項は記号に続けて、数値か括弧で囲んだ式で書かれている
+                    self.operand()
+                } else {
+                    Err(self.unexpected())
+                }
+            }
+        }
+    }
+
+    /// 結合文字の上線が付いた範囲を読み、上線を除いた文字列を返す。上線がなければ`None`
+    fn overlined(&mut self) -> Option<String> {
+        let is_combining = |c: char| ('\u{0300}'..='\u{036f}').contains(&c);
+        let mut text = String::new();
+        let mut chars = self.rest().char_indices().peekable();
+        let mut end = 0;
+        while let Some((_, c)) = chars.next() {
+            // 文字とそれに続く結合文字をひとまとまりとし、最後の結合文字が上線なら範囲に含める
+            let mut cluster = vec![c];
+            while let Some((_, mark)) = chars.next_if(|(_, mark)| is_combining(*mark)) {
+                cluster.push(mark);
+            }
+            if cluster.len() < 2 || cluster.last() != Some(&'\u{0305}') {
+                break;
+            }
+            cluster.pop();
+            text.extend(cluster);
+            end = chars.peek().map_or(self.rest().len(), |(i, _)| *i);
+        }
+        if text.is_empty() {
+            return None;
+        }
+        self.position += end;
+        Some(text)
+    }
+
+    /// 記号で始まる単項演算子（`√x`, `⌊x⌋`）を読む
+    fn prefixed_unary(&mut self, op: &UnaryOpPrinter) -> Result<Parsed, ParseError> {
+        self.expect(&op.prefix)?;
+        let expr = if op.suffix.trim().is_empty() {
+            // 同じ優先度の演算子は項に含める（`-4^4`は`-(4^4)`と読む）
+            let limit = if op.paren {
+                bound(op.precedence, true)
+            } else {
+                i32::MAX
+            };
+            self.scripted(&op.script, limit)?
+        } else {
+            let expr = self.scripted(&op.script, i32::MAX)?;
+            self.expect(&op.suffix)?;
+            expr
+        };
+        self.apply_unary(op, expr)
+    }
+
+    /// 記号または上付き文字などの項で始まる二項演算子（`C(n, k)`, `log₄(x)`, `⁴√x`）を読む
+    fn prefixed_binary(&mut self, op: &BinaryOpPrinter) -> Result<Parsed, ParseError> {
+        self.expect(&op.prefix)?;
+        let limit = if op.paren_left {
+            bound(op.precedence, op.left_associative)
+        } else {
+            i32::MAX
+        };
+        let lhs = self.scripted(&op.left_script, limit)?;
+        self.expect(&op.infix)?;
+        self.binary_rest(op, lhs)
+    }
+
+    /// 二項演算子の右の項と、それに続く記号を読んで演算子を適用する
+    fn binary_rest(&mut self, op: &BinaryOpPrinter, lhs: Parsed) -> Result<Parsed, ParseError> {
+        let limit = if op.paren_right {
+            bound(op.precedence, op.right_associative && !op.left_associative)
+        } else {
+            i32::MAX
+        };
+        let rhs = self.scripted(&op.right_script, limit)?;
+        self.expect(&op.suffix)?;
+        if op.swapped {
+            self.apply_binary(op, rhs, lhs)
+        } else {
+            self.apply_binary(op, lhs, rhs)
+        }
+    }
+
+    /// 項に続く演算子（`x!`, `x+y`, `xʸ`）があれば読む。優先度が`limit`を超える演算子は読まない
+    fn postfix(&mut self, lhs: &Parsed, limit: i32) -> Result<Option<Parsed>, ParseError> {
+        self.skip_whitespace();
+        let printer = &self.parser.printer;
+        let rest = self.rest();
+
+        // 記号の最も長いものを使う
+        let unary = printer
+            .unary_ops()
+            .iter()
+            .filter(|op| op.prefix.is_empty() && !op.suffix.trim().is_empty())
+            .filter(|op| matched(rest, op.suffix.trim()).is_some())
+            .max_by_key(|op| op.suffix.trim().len());
+        let binary = printer
+            .binary_ops()
+            .iter()
+            .filter(|op| op.prefix.is_empty() && !is_raised(&op.left_script))
+            .filter(|op| match op.infix.trim() {
+                // 記号がなければ、右の項が上付き文字などで書かれている
+                "" => match &op.right_script {
+                    Script::Subscript(fallback) | Script::Superscript(fallback) => {
+                        rest.chars()
+                            .next()
+                            .is_some_and(|c| op.right_script.restore(c).is_some())
+                            || !fallback.is_empty() && matched(rest, fallback).is_some()
+                    }
+                    _ => false,
+                },
+                infix => matched(rest, infix).is_some(),
+            })
+            .max_by_key(|op| op.infix.trim().len());
+
+        match (unary, binary) {
+            (Some(op), binary)
+                if binary.is_none_or(|b| b.infix.trim().len() < op.suffix.trim().len()) =>
+            {
+                if !within(op.precedence, limit) {
+                    return Ok(None);
+                }
+                self.expect(&op.suffix)?;
+                self.apply_unary(op, lhs.clone()).map(Some)
+            }
+            (_, Some(op)) => {
+                if !within(op.precedence, limit) {
+                    return Ok(None);
+                }
+                self.expect(&op.infix)?;
+                self.binary_rest(op, lhs.clone()).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn apply_unary(&self, op: &UnaryOpPrinter, expr: Parsed) -> Result<Parsed, ParseError> {
+        let (name, unary_op) = self
+            .parser
+            .unary_ops
+            .get(&op.token)
+            .ok_or_else(|| self.unexpected())?;
+        let equation = Equation::apply_unary(&expr.0, unary_op)
+            .ok_or_else(|| ParseError::NotApplicable(name.clone()))?;
+        Ok((equation, op.precedence))
+    }
+
+    fn apply_binary(
+        &self,
+        op: &BinaryOpPrinter,
+        lhs: Parsed,
+        rhs: Parsed,
+    ) -> Result<Parsed, ParseError> {
+        let (name, binary_op) = self
+            .parser
+            .binary_ops
+            .get(&op.token)
+            .ok_or_else(|| self.unexpected())?;
+        let equation = Equation::apply_binary(&lhs.0, &rhs.0, binary_op)
+            .ok_or_else(|| ParseError::NotApplicable(name.clone()))?;
+        Ok((equation, op.precedence))
+    }
+}
+
+/// `text`が記号で始まっていれば、その長さ（バイト数）を返す。
+/// 上付き文字などの`⁻`はどちらにも戻せないので、`-`と`−`は同じ記号とみなす
+fn matched(text: &str, symbol: &str) -> Option<usize> {
+    let mut chars = text.char_indices();
+    let mut len = 0;
+    for s in symbol.chars() {
+        match chars.next() {
+            Some((i, c)) if c == s || "-−".contains(c) && "-−".contains(s) => {
+                len = i + c.len_utf8();
+            }
+            _ => return None,
+        }
+    }
+    Some(len)
+}
+
+/// 上付き・下付き文字で書かれる項かどうか
+fn is_raised(script: &Script) -> bool {
+    matches!(script, Script::Subscript(_) | Script::Superscript(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        preset::Preset,
+        print::{Parenthesization, PrintStyle},
+        registry::{catalogue, Registry},
+    };
+
+    fn parser() -> Parser {
+        Registry::standard().parser(PrintStyle::Standard)
+    }
+
+    fn tokens(parser: &Parser, text: &str) -> Vec<Token> {
+        parser
+            .parse(text)
+            .unwrap_or_else(|err| panic!("{}: {}", text, err))
+            .tokens
+    }
+
+    #[test]
+    fn preset_results_round_trip() {
+        let styles = [PrintStyle::Standard, PrintStyle::Unicode, PrintStyle::Ascii];
+        for name in Preset::NAMES {
+            let preset = Preset::by_name(name).unwrap();
+            // 目標の値が決まっているプリセットでは、それを作れる数字を使う
+            let numbers = if matches!(name, "24" | "make10") {
+                "1234"
+            } else {
+                "444"
+            };
+            let (results, _) = preset.search_int(numbers);
+            assert!(!results.is_empty(), "{}", name);
+            for style in styles {
+                let printer = preset.style_printer(style);
+                let parser = preset.parser(style);
+                for (n, e) in &results {
+                    let text = printer.print(e).unwrap();
+                    let parsed = parser
+                        .parse(&text)
+                        .unwrap_or_else(|err| panic!("{}: {} = {}: {}", name, n, text, err));
+                    assert_eq!(
+                        printer.print(&parsed).unwrap(),
+                        text,
+                        "{}: {} = {}",
+                        name,
+                        n,
+                        text
+                    );
+                    assert!(
+                        (parsed.value - *n as f64).abs() < 1e-9,
+                        "{}: {} = {} is read as {}",
+                        name,
+                        n,
+                        text,
+                        parsed.value
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_parenthesization_reads_back_to_the_same_value() {
+        for name in ["standard", "liberal"] {
+            let preset = Preset::by_name(name).unwrap();
+            let (results, _) = preset.search_int("444");
+            let parser = preset.parser(PrintStyle::Standard);
+            for parenthesization in [Parenthesization::Full, Parenthesization::Semantic] {
+                let printer = preset
+                    .style_printer(PrintStyle::Standard)
+                    .parenthesization(parenthesization);
+                for (n, e) in &results {
+                    let text = printer.print(e).unwrap();
+                    let parsed = parser
+                        .parse(&text)
+                        .unwrap_or_else(|err| panic!("{}: {} = {}: {}", name, n, text, err));
+                    assert!(
+                        (parsed.value - *n as f64).abs() < 1e-9,
+                        "{}: {} = {} is read as {}",
+                        name,
+                        n,
+                        text,
+                        parsed.value
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn left_associative_operators_apply_left_to_right() {
+        let parser = parser();
+        // (4-4)-4
+        assert_eq!(tokens(&parser, "4-4-4"), [0xe4, 0xe4, 0x11, 0xe4, 0x11]);
+        assert_eq!(parser.parse("4-4-4").unwrap().value, -4f64);
+        // (4/4)/4
+        assert_eq!(tokens(&parser, "4/4/4"), [0xe4, 0xe4, 0x13, 0xe4, 0x13]);
+    }
+
+    #[test]
+    fn right_associative_operators_apply_right_to_left() {
+        let parser = parser();
+        // 4^(4^4)
+        assert_eq!(tokens(&parser, "4^4^4"), [0xe4, 0xe4, 0xe4, 0x14, 0x14]);
+        assert_eq!(tokens(&parser, "(4^4)^4"), [0xe4, 0xe4, 0x14, 0xe4, 0x14]);
+    }
+
+    #[test]
+    fn precedence_decides_operands() {
+        let parser = parser();
+        // 4+(4*4)
+        assert_eq!(tokens(&parser, "4+4*4"), [0xe4, 0xe4, 0xe4, 0x12, 0x10]);
+        // -(4^4)
+        assert_eq!(tokens(&parser, "-4^4"), [0xe4, 0xe4, 0x14, 0x00]);
+        assert_eq!(tokens(&parser, "-(4^4)"), [0xe4, 0xe4, 0x14, 0x00]);
+        assert_eq!(tokens(&parser, "(-4)^4"), [0xe4, 0x00, 0xe4, 0x14]);
+        // (-4)*4
+        assert_eq!(tokens(&parser, "-4*4"), [0xe4, 0x00, 0xe4, 0x12]);
+        // (√4)^4
+        assert_eq!(tokens(&parser, "√4^4"), [0xe4, 0x01, 0xe4, 0x14]);
+        // 空白は無視する
+        assert_eq!(tokens(&parser, " 4 + 4 * 4 "), tokens(&parser, "4+4*4"));
+    }
+
+    #[test]
+    fn prefix_and_suffix_operators_share_a_symbol() {
+        let mut registry = Registry::standard();
+        registry.add_unary(catalogue::subfact()).unwrap();
+        let parser = registry.parser(PrintStyle::Standard);
+        // !4
+        assert_eq!(tokens(&parser, "!4"), [0xe4, 0x33]);
+        assert_eq!(parser.parse("!4").unwrap().value, 9f64);
+        // 4!
+        assert_eq!(tokens(&parser, "4!"), [0xe4, 0x02]);
+        // (!4)+(4!)
+        assert_eq!(tokens(&parser, "!4+4!"), [0xe4, 0x33, 0xe4, 0x02, 0x10]);
+        assert_eq!(parser.parse("!4+4!").unwrap().value, 33f64);
+
+        let printer = registry.printer();
+        for e in [[0xe3, 0x02, 0x33], [0xe3, 0x33, 0x02]] {
+            let equation = Equation {
+                tokens: e.to_vec(),
+                cost: 0,
+                value: 0f64,
+            };
+            let text = printer.print(&equation).unwrap();
+            assert_eq!(tokens(&parser, &text), e, "{}", text);
+        }
+    }
+
+    #[test]
+    fn errors_report_positions() {
+        let parser = parser();
+        assert_eq!(
+            parser.parse("4+*4").unwrap_err(),
+            (ParseError::UnexpectedChar(2, '*'))
+        );
+        assert_eq!(
+            parser.parse("(4+4))").unwrap_err(),
+            (ParseError::UnexpectedChar(5, ')'))
+        );
+        assert_eq!(
+            parser.parse("4 + x").unwrap_err(),
+            (ParseError::UnexpectedChar(4, 'x'))
+        );
+        assert_eq!(parser.parse("4+").unwrap_err(), (ParseError::UnexpectedEnd));
+        assert_eq!(
+            parser.parse("(4+4").unwrap_err(),
+            (ParseError::UnexpectedEnd)
+        );
+        assert_eq!(parser.parse("").unwrap_err(), (ParseError::UnexpectedEnd));
+        assert_eq!(
+            parser.parse("4+4444444444").unwrap_err(),
+            (ParseError::InvalidNumber(2, "4444444444".to_string()))
+        );
+        assert_eq!(
+            parser.parse("√(-4)").unwrap_err(),
+            (ParseError::NotApplicable("sqrt".to_string()))
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! 逆ポーランド記法のトークン列（`Equation::tokens`）をたどり、数値と演算子の適用に分ける
+//!
+//! 表示、構文木、検算はいずれもこのたどり方を使うので、不正なトークン列を同じように扱う。
+//! 数値は続く桁や小数点をすべて読んでから`Visitor::number`に渡す
+
+use crate::search::{Token, DECIMAL_POINT};
+
+/// 演算子の項の数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Unary,
+    Binary,
+}
+
+/// トークン列として正しくない
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenError {
+    /// 演算子として登録されていないトークン
+    UnknownToken(usize, Token),
+    /// 演算子の項が足りない
+    MissingOperand(usize),
+    /// 小数点の付いていない数値に続かない、数値二桁目以降のトークンや小数点
+    MisplacedDigit(usize),
+    /// 空のトークン列
+    Empty,
+    /// 演算子に使われなかった項が残っている
+    LeftoverOperands(usize),
+}
+
+/// トークン列をたどって、部分式ごとの結果を組み立てる
+pub trait Visitor {
+    type Output;
+    type Error: From<TokenError>;
+
+    /// 演算子のトークンの項の数。演算子でなければ`None`
+    fn arity(&self, token: Token) -> Option<Arity>;
+
+    /// 数値。`digits`は数字の並びで、`decimal`なら先頭に小数点が付く（`.44`）
+    fn number(&self, digits: &str, decimal: bool) -> Result<Self::Output, Self::Error>;
+
+    /// `position`番目のトークンの単項演算子を適用する
+    fn unary(
+        &self,
+        position: usize,
+        token: Token,
+        operand: Self::Output,
+    ) -> Result<Self::Output, Self::Error>;
+
+    /// `position`番目のトークンの二項演算子を適用する。`lhs`, `rhs`はトークン列での順
+    fn binary(
+        &self,
+        position: usize,
+        token: Token,
+        lhs: Self::Output,
+        rhs: Self::Output,
+    ) -> Result<Self::Output, Self::Error>;
+}
+
+/// スタックに積む項。数値は続く桁や小数点を読むまで確定しない
+enum Item<T> {
+    Number { digits: String, decimal: bool },
+    Expr(T),
+}
+
+impl<T> Item<T> {
+    fn finish<V: Visitor<Output = T>>(self, visitor: &V) -> Result<T, V::Error> {
+        match self {
+            Item::Number { digits, decimal } => visitor.number(&digits, decimal),
+            Item::Expr(expr) => Ok(expr),
+        }
+    }
+}
+
+/// トークン列を先頭からたどり、式全体の結果を返す
+pub fn walk<V: Visitor>(tokens: &[Token], visitor: &V) -> Result<V::Output, V::Error> {
+    let mut stack = Vec::<Item<V::Output>>::new();
+
+    for (position, &token) in tokens.iter().enumerate() {
+        match token {
+            0xe0..=0xef => {
+                // 数値一桁目
+                stack.push(Item::Number {
+                    digits: (token - 0xe0).to_string(),
+                    decimal: false,
+                });
+            }
+            0xf0..=0xff | DECIMAL_POINT => {
+                // 数値二桁目以降と小数点は、小数点の付いていない数値にだけ続く
+                let Some(Item::Number {
+                    digits,
+                    decimal: decimal @ false,
+                }) = stack.last_mut()
+                else {
+                    return Err(TokenError::MisplacedDigit(position).into());
+                };
+                if token == DECIMAL_POINT {
+                    *decimal = true;
+                } else {
+                    digits.push_str(&(token - 0xf0).to_string());
+                }
+            }
+            _ => {
+                let expr = match visitor.arity(token) {
+                    Some(Arity::Unary) => {
+                        let operand = stack.pop().ok_or(TokenError::MissingOperand(position))?;
+                        visitor.unary(position, token, operand.finish(visitor)?)?
+                    }
+                    Some(Arity::Binary) => {
+                        let (Some(rhs), Some(lhs)) = (stack.pop(), stack.pop()) else {
+                            return Err(TokenError::MissingOperand(position).into());
+                        };
+                        let lhs = lhs.finish(visitor)?;
+                        let rhs = rhs.finish(visitor)?;
+                        visitor.binary(position, token, lhs, rhs)?
+                    }
+                    None => return Err(TokenError::UnknownToken(position, token).into()),
+                };
+                stack.push(Item::Expr(expr));
+            }
+        }
+    }
+
+    match stack.len() {
+        0 => Err(TokenError::Empty.into()),
+        1 => stack.pop().unwrap().finish(visitor),
+        n => Err(TokenError::LeftoverOperands(n - 1).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 部分式を括弧で囲んで並べる
+    struct Parenthesize;
+
+    impl Visitor for Parenthesize {
+        type Output = String;
+        type Error = TokenError;
+
+        fn arity(&self, token: Token) -> Option<Arity> {
+            match token {
+                0x00 => Some(Arity::Unary),
+                0x10 => Some(Arity::Binary),
+                _ => None,
+            }
+        }
+
+        fn number(&self, digits: &str, decimal: bool) -> Result<String, TokenError> {
+            Ok(if decimal { "." } else { "" }.to_string() + digits)
+        }
+
+        fn unary(&self, _: usize, _: Token, operand: String) -> Result<String, TokenError> {
+            Ok(format!("(-{})", operand))
+        }
+
+        fn binary(
+            &self,
+            _: usize,
+            _: Token,
+            lhs: String,
+            rhs: String,
+        ) -> Result<String, TokenError> {
+            Ok(format!("({}+{})", lhs, rhs))
+        }
+    }
+
+    #[test]
+    fn numbers_are_read_to_the_end() {
+        // 44+.4
+        let tokens = [0xe4, 0xf4, 0xe4, DECIMAL_POINT, 0x10];
+        assert_eq!(walk(&tokens, &Parenthesize), Ok("(44+.4)".to_string()));
+        // -(.44)
+        let tokens = [0xe4, 0xf4, DECIMAL_POINT, 0x00];
+        assert_eq!(walk(&tokens, &Parenthesize), Ok("(-.44)".to_string()));
+    }
+
+    #[test]
+    fn malformed_token_sequences() {
+        assert_eq!(walk(&[], &Parenthesize), Err(TokenError::Empty));
+        assert_eq!(
+            walk(&[0xe4, 0x10], &Parenthesize),
+            Err(TokenError::MissingOperand(1))
+        );
+        assert_eq!(
+            walk(&[0xe4, 0x00, 0xf4], &Parenthesize),
+            Err(TokenError::MisplacedDigit(2))
+        );
+        // 小数点の後に桁や小数点は続かない
+        assert_eq!(
+            walk(&[0xe4, DECIMAL_POINT, 0xf4], &Parenthesize),
+            Err(TokenError::MisplacedDigit(2))
+        );
+        assert_eq!(
+            walk(&[0xe4, DECIMAL_POINT, DECIMAL_POINT], &Parenthesize),
+            Err(TokenError::MisplacedDigit(2))
+        );
+        assert_eq!(
+            walk(&[0xe4, 0xe4], &Parenthesize),
+            Err(TokenError::LeftoverOperands(1))
+        );
+        assert_eq!(
+            walk(&[0xe4, 0x7f], &Parenthesize),
+            Err(TokenError::UnknownToken(1, 0x7f))
+        );
+    }
+}
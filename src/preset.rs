@@ -0,0 +1,345 @@
+use crate::{
+    ast::AstBuilder,
+    evaluate::Evaluator,
+    parse::Parser,
+    print::{pretty::PrettyPrinter, symbolic::SymbolicPrinter, PrintStyle, Printer},
+    registry::{catalogue::*, BinaryOpDef, Registry, UnaryOpDef},
+    search::{Concatenation, Equation, SearchStats, Searcher, Value},
+    search_int_by, search_int_permuted,
+};
+use std::collections::HashMap;
+
+/// 遊び方ごとのルール一式。演算子とそのコスト、数字の連結方法、探索の制限をまとめて指定する
+#[derive(Debug, Clone)]
+pub struct Preset {
+    /// プリセット名
+    pub name: String,
+    /// 使用する演算子とそのコスト
+    pub registry: Registry,
+    pub concatenation: Concatenation,
+    /// 単項演算を繰り返し適用する回数
+    pub unary_rounds: usize,
+    /// 保持する値の絶対値の上限
+    pub max_value: Option<Value>,
+    /// 保持する式のコストの上限
+    pub max_cost: Option<u8>,
+    /// `x * 1`や`x + 0`のような単位元との演算を候補から除くかどうか。
+    /// 桁の消費に必要な式まで除いてしまうので、どのプリセットでも無効にしている（`Searcher::prune_identity`）
+    pub prune_identity: bool,
+    /// 数字の並べ替えを許すかどうか
+    pub permute: bool,
+    /// 作るべき数（24ゲームの24など）。`None`ならすべての整数が対象
+    pub target: Option<i32>,
+}
+
+impl Preset {
+    /// 選択可能なプリセット名
+    pub const NAMES: [&'static str; 9] = [
+        "standard", "classic", "strict", "liberal", "anything", "integer", "c", "24", "make10",
+    ];
+
+    pub fn by_name(name: &str) -> Option<Preset> {
+        match name {
+            "standard" => Some(Preset::standard()),
+            "classic" => Some(Preset::classic()),
+            "strict" => Some(Preset::strict()),
+            "liberal" => Some(Preset::liberal()),
+            "anything" => Some(Preset::anything()),
+            "integer" => Some(Preset::integer()),
+            "c" => Some(Preset::c()),
+            "24" => Some(Preset::game24()),
+            "make10" => Some(Preset::make10()),
+            _ => None,
+        }
+    }
+
+    fn new(name: &str, registry: Registry) -> Preset {
+        Preset {
+            name: name.to_string(),
+            registry,
+            concatenation: Concatenation::Integer,
+            unary_rounds: 3,
+            max_value: None,
+            max_cost: None,
+            prune_identity: false,
+            permute: false,
+            target: None,
+        }
+    }
+
+    /// 標準の演算子と整数の連結
+    pub fn standard() -> Preset {
+        Preset::new("standard", Registry::standard())
+    }
+
+    /// 古典的なフォー・フォーズ（四則演算、連結、小数、`√`、`!`）
+    pub fn classic() -> Preset {
+        Preset {
+            concatenation: Concatenation::Decimal,
+            ..Preset::new(
+                "classic",
+                registry(
+                    vec![negate(), sqrt(), fact()],
+                    vec![add(), sub(), mul(), div()],
+                ),
+            )
+        }
+    }
+
+    /// 四則演算のみで、数字の連結もしない
+    pub fn strict() -> Preset {
+        Preset {
+            concatenation: Concatenation::None,
+            unary_rounds: 0,
+            ..Preset::new("strict", registry(vec![], vec![add(), sub(), mul(), div()]))
+        }
+    }
+
+    /// 標準の演算子に加えて小数、丸め演算、対数・指数関数、二重階乗も使う。値とコストの爆発を防ぐために上限を設ける
+    pub fn liberal() -> Preset {
+        Preset {
+            concatenation: Concatenation::Decimal,
+            max_value: Some(1e6),
+            max_cost: Some(20),
+            unary_rounds: 2,
+            ..Preset::new(
+                "liberal",
+                registry(
+                    vec![
+                        negate(),
+                        sqrt(),
+                        fact(),
+                        double_fact(),
+                        floor(),
+                        ceil(),
+                        round(),
+                        ln(),
+                        log10(),
+                        exp(),
+                    ],
+                    vec![add(), sub(), mul(), div(), pow(), log()],
+                ),
+            )
+        }
+    }
+
+    /// `liberal`にガンマ関数と度数法の三角関数を加えた、何でもありのルール
+    pub fn anything() -> Preset {
+        let mut preset = Preset::liberal();
+        preset.name = "anything".to_string();
+        for def in [gamma(), sin(), cos(), tan()] {
+            preset.registry.add_unary(def).unwrap();
+        }
+        preset
+    }
+
+    /// 整数演算のみ（`/`の代わりに`div`と`mod`、`gcd`、`lcm`を使う）。途中の値もすべて整数になる
+    pub fn integer() -> Preset {
+        Preset::new(
+            "integer",
+            registry(
+                vec![negate(), fact()],
+                vec![add(), sub(), mul(), int_div(), modulo(), gcd(), lcm()],
+            ),
+        )
+    }
+
+    /// C言語の32ビット整数の演算子と優先順位（`^`はXOR）
+    pub fn c() -> Preset {
+        let width = BitWidth::W32;
+        // C言語の二項演算子はすべて左から順に結合するので、`4+(4-4)`や`4*(4%4)`の括弧は省略しない
+        let (mut add, mut mul) = (add(), mul());
+        add.printer.right_associative = false;
+        mul.printer.right_associative = false;
+        Preset::new(
+            "c",
+            registry(
+                vec![negate(), bit_not(width)],
+                vec![
+                    add,
+                    sub(),
+                    mul,
+                    trunc_div(),
+                    rem(),
+                    bit_and(width),
+                    bit_or(width),
+                    bit_xor(width),
+                    shl(width),
+                    shr(width),
+                ],
+            ),
+        )
+    }
+
+    /// 24ゲーム（四則演算のみ、数字は並べ替え可、連結なしで24を作る）
+    pub fn game24() -> Preset {
+        Preset {
+            target: Some(24),
+            ..Preset::make_n("24")
+        }
+    }
+
+    /// テンパズル（四則演算のみ、数字は並べ替え可、連結なしで10を作る）
+    pub fn make10() -> Preset {
+        Preset {
+            target: Some(10),
+            ..Preset::make_n("make10")
+        }
+    }
+
+    fn make_n(name: &str) -> Preset {
+        let mut registry = registry(vec![], vec![add(), sub(), mul(), div()]);
+        // 演算子の種類によらず、演算の少ない式を優先する
+        for name in ["add", "sub", "mul", "div"] {
+            registry.set_cost(name, 1).unwrap();
+        }
+        Preset {
+            concatenation: Concatenation::None,
+            unary_rounds: 0,
+            permute: true,
+            ..Preset::new(name, registry)
+        }
+    }
+
+    pub fn searcher(&self) -> Searcher {
+        self.registry
+            .searcher()
+            .concatenation(self.concatenation)
+            .unary_rounds(self.unary_rounds)
+            .max_value(self.max_value)
+            .max_cost(self.max_cost)
+            .prune_identity(self.prune_identity)
+    }
+
+    pub fn printer(&self) -> Printer {
+        self.registry.printer()
+    }
+
+    pub fn ascii_printer(&self) -> Printer {
+        self.registry.ascii_printer()
+    }
+
+    pub fn style_printer(&self, style: PrintStyle) -> Printer {
+        self.registry.style_printer(style)
+    }
+
+    pub fn latex_printer(&self) -> Printer {
+        self.registry.latex_printer()
+    }
+
+    pub fn mathml_printer(&self) -> Printer {
+        self.registry.mathml_printer()
+    }
+
+    pub fn pretty_printer(&self, style: PrintStyle) -> PrettyPrinter {
+        self.registry.pretty_printer(style)
+    }
+
+    pub fn ast_builder(&self) -> AstBuilder {
+        self.registry.ast_builder()
+    }
+
+    pub fn evaluator(&self) -> Evaluator {
+        self.registry.evaluator()
+    }
+
+    pub fn parser(&self, style: PrintStyle) -> Parser {
+        self.registry.parser(style)
+    }
+
+    pub fn rpn_printer(&self) -> SymbolicPrinter {
+        self.registry.rpn_printer()
+    }
+
+    pub fn sexp_printer(&self) -> SymbolicPrinter {
+        self.registry.sexp_printer()
+    }
+
+    /// このルールで探索し、整数値となる式と探索の統計を返す。`target`があればその値のみを返す
+    pub fn search_int(&self, numbers: &str) -> (HashMap<i32, Equation>, SearchStats) {
+        let searcher = self.searcher();
+        let (mut results, stats) = if self.permute {
+            search_int_permuted(&searcher, numbers)
+        } else {
+            search_int_by(&searcher, numbers)
+        };
+        if let Some(target) = self.target {
+            results.retain(|n, _| *n == target);
+        }
+        (results, stats)
+    }
+}
+
+fn registry(unary_ops: Vec<UnaryOpDef>, binary_ops: Vec<BinaryOpDef>) -> Registry {
+    let mut registry = Registry::new();
+    for def in unary_ops {
+        registry.add_unary(def).unwrap();
+    }
+    for def in binary_ops {
+        registry.add_binary(def).unwrap();
+    }
+    registry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::Verification;
+
+    /// 探索結果をすべて表示して読み戻し、同じ値の式になることを確かめる
+    fn assert_round_trip(preset: &Preset, numbers: &str) {
+        let printer = preset.printer();
+        let parser = preset.parser(PrintStyle::Standard);
+        let (results, _) = preset.search_int(numbers);
+        assert!(!results.is_empty());
+        for (n, e) in results {
+            let text = printer.print(&e).unwrap();
+            let parsed = parser
+                .parse(&text)
+                .unwrap_or_else(|err| panic!("{} = {}: {}", n, text, err));
+            assert!(
+                (parsed.value - n as Value).abs() < 1e-9,
+                "{} = {} is read as {}",
+                n,
+                text,
+                parsed.value
+            );
+        }
+    }
+
+    #[test]
+    fn integer_results_round_trip() {
+        assert_round_trip(&Preset::integer(), "4437");
+    }
+
+    #[test]
+    fn liberal_results_round_trip() {
+        assert_round_trip(&Preset::liberal(), "444");
+    }
+
+    #[test]
+    fn c_results_round_trip() {
+        assert_round_trip(&Preset::c(), "4437");
+    }
+
+    #[test]
+    fn game24_accepts_values_just_below_24() {
+        // 8/(3-8/3) は浮動小数点では 23.99999999999999 になる
+        let (results, _) = Preset::game24().search_int("3388");
+        let e = &results[&24];
+        assert_eq!(Preset::game24().printer().print(e).unwrap(), "8/(3-8/3)");
+        assert!(e.value < 24f64);
+        let verification = Preset::game24().evaluator().verify(24, e).unwrap();
+        assert_eq!(verification, Verification::Exact);
+    }
+
+    #[test]
+    fn make10_uses_fractional_intermediates() {
+        // 8/(1-1/5) 以外に10を作る方法はない
+        let (results, _) = Preset::make10().search_int("1158");
+        let e = &results[&10];
+        assert_eq!(Preset::make10().printer().print(e).unwrap(), "8/(1-1/5)");
+        let verification = Preset::make10().evaluator().verify(10, e).unwrap();
+        assert_eq!(verification, Verification::Exact);
+    }
+}
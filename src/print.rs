@@ -1,14 +1,100 @@
-use super::search::{Equation, Token};
+pub mod pretty;
+pub mod symbolic;
+
+use super::{
+    postfix::{self, Arity, TokenError, Visitor},
+    search::{BinaryOp, Equation, Token, UnaryOp, DECIMAL_POINT},
+};
+use std::{error, fmt};
+
+/// 式を表示できない、または`Printer`が演算子の一覧と対応していない
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrintError {
+    /// 表示方法のないトークン
+    UnknownToken(usize, Token),
+    /// 演算子の項が足りない
+    MissingOperand(usize),
+    /// 数値の途中以外に現れた数値二桁目以降のトークンや小数点
+    MisplacedDigit(usize),
+    /// 空のトークン列
+    Empty,
+    /// 演算子に使われなかった項が残っている
+    LeftoverOperands(usize),
+    /// 演算子の一覧にある演算子の表示方法がない
+    MissingOperator(Token),
+    /// 演算子の一覧にない演算子の表示方法がある
+    UnknownOperator(Token),
+    /// 同じトークンの表示方法が複数ある
+    DuplicateOperator(Token),
+    /// 桁や小数点のために予約されたトークンの表示方法がある
+    ReservedToken(Token),
+}
+
+impl fmt::Display for PrintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrintError::UnknownToken(position, token) => {
+                write!(f, "Unknown token {:#04x} at {}", token, position)
+            }
+            PrintError::MissingOperand(position) => {
+                write!(f, "Operator at {} is missing an operand", position)
+            }
+            PrintError::MisplacedDigit(position) => {
+                write!(
+                    f,
+                    "Digit or decimal point at {} does not follow a number",
+                    position
+                )
+            }
+            PrintError::Empty => write!(f, "Empty token sequence"),
+            PrintError::LeftoverOperands(n) => {
+                write!(f, "{} operands are left without an operator", n)
+            }
+            PrintError::MissingOperator(token) => {
+                write!(f, "Operator {:#04x} has no printer", token)
+            }
+            PrintError::UnknownOperator(token) => {
+                write!(f, "Printer for {:#04x} has no matching operator", token)
+            }
+            PrintError::DuplicateOperator(token) => {
+                write!(f, "Operator {:#04x} has more than one printer", token)
+            }
+            PrintError::ReservedToken(token) => {
+                write!(
+                    f,
+                    "Token {:#04x} is reserved for digits and the decimal point",
+                    token
+                )
+            }
+        }
+    }
+}
+
+impl error::Error for PrintError {}
+
+impl From<TokenError> for PrintError {
+    fn from(e: TokenError) -> Self {
+        match e {
+            TokenError::UnknownToken(position, token) => PrintError::UnknownToken(position, token),
+            TokenError::MissingOperand(position) => PrintError::MissingOperand(position),
+            TokenError::MisplacedDigit(position) => PrintError::MisplacedDigit(position),
+            TokenError::Empty => PrintError::Empty,
+            TokenError::LeftoverOperands(n) => PrintError::LeftoverOperands(n),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct UnaryOpPrinter {
-    token: Token,
-    prefix: String,
-    suffix: String,
+    pub(crate) token: Token,
+    pub(crate) prefix: String,
+    pub(crate) suffix: String,
     /// 演算子優先度
-    precedence: i32,
+    pub(crate) precedence: i32,
     /// 括弧が必要？
-    paren: bool,
+    pub(crate) paren: bool,
+    /// 項の表示方法
+    pub(crate) script: Script,
 }
 
 impl UnaryOpPrinter {
@@ -25,25 +111,158 @@ impl UnaryOpPrinter {
             suffix: suffix.into(),
             precedence,
             paren,
+            script: Script::Normal,
         }
     }
+
+    /// 項の表示方法を指定する
+    pub fn script(mut self, script: Script) -> UnaryOpPrinter {
+        self.script = script;
+        self
+    }
+}
+
+/// 式の表示スタイル
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PrintStyle {
+    /// 演算子ごとの標準の表示（`√`と`*`、`^`などが混在する）
+    #[default]
+    Standard,
+    /// できるだけ数学の記法に近いUnicode文字で表示する（`×`, `÷`, `−`, `4⁴`, `√4̅+̅4̅`）
+    Unicode,
+    /// ASCII文字だけで表示する（`sqrt(4)`, `*`, `^`）
+    Ascii,
+}
+
+impl PrintStyle {
+    /// 選択可能なスタイル名
+    pub const NAMES: [&'static str; 3] = ["standard", "unicode", "ascii"];
+
+    pub fn by_name(name: &str) -> Option<PrintStyle> {
+        match name {
+            "standard" => Some(PrintStyle::Standard),
+            "unicode" => Some(PrintStyle::Unicode),
+            "ascii" => Some(PrintStyle::Ascii),
+            _ => None,
+        }
+    }
+}
+
+/// 括弧の付け方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Parenthesization {
+    /// 演算子の優先度と結合性の指定から、必要な括弧だけを付ける（`4-(4-4)`）
+    #[default]
+    Minimal,
+    /// 演算子を適用した項をすべて括弧で囲む（`(4+4)+4`）。関数呼び出しの形など、それ自体で範囲が明らかな項は除く
+    Full,
+    /// `Minimal`に加えて、演算子の結合性と逆演算の指定から、値の変わらない括弧を省く
+    /// （右結合の指定がなくても`4+(4-4)`を`4+4-4`とする）。
+    /// また、`-(4*4)`を`-4*4`とするように、左の項に移せる単項演算子の項の括弧も省く。
+    /// 省いた式を読み戻すと、値は同じだが演算の順序の異なる式になる
+    Semantic,
+}
+
+impl Parenthesization {
+    /// 選択可能な括弧の付け方の名前
+    pub const NAMES: [&'static str; 3] = ["minimal", "full", "semantic"];
+
+    pub fn by_name(name: &str) -> Option<Parenthesization> {
+        match name {
+            "minimal" => Some(Parenthesization::Minimal),
+            "full" => Some(Parenthesization::Full),
+            "semantic" => Some(Parenthesization::Semantic),
+            _ => None,
+        }
+    }
+}
+
+/// 項を上付き・下付き文字などで表示するかどうか
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Script {
+    #[default]
+    Normal,
+    /// 括弧が必要な場合に、括弧の代わりに結合文字の上線で範囲を示す（`√4̅+̅4̅`）
+    Overline,
+    /// 下付き文字（`log₄`）。変換できない文字を含む場合は、指定した記号に続けて通常の文字で表示する（`log_(√4)`）
+    Subscript(String),
+    /// 上付き文字（`⁴√`）。変換できない文字を含む場合は、指定した記号に続けて通常の文字で表示する
+    Superscript(String),
+}
+
+impl Script {
+    /// 通常の文字と、それに対応する下付き文字・上付き文字
+    const CHARS: [(char, char, char); 14] = [
+        ('0', '₀', '⁰'),
+        ('1', '₁', '¹'),
+        ('2', '₂', '²'),
+        ('3', '₃', '³'),
+        ('4', '₄', '⁴'),
+        ('5', '₅', '⁵'),
+        ('6', '₆', '⁶'),
+        ('7', '₇', '⁷'),
+        ('8', '₈', '⁸'),
+        ('9', '₉', '⁹'),
+        ('+', '₊', '⁺'),
+        ('-', '₋', '⁻'),
+        ('(', '₍', '⁽'),
+        (')', '₎', '⁾'),
+    ];
+
+    /// 上付き・下付き文字に変換する。変換できない文字を含む場合は`None`
+    fn convert(&self, s: &str) -> Option<String> {
+        if let Script::Normal | Script::Overline = self {
+            return Some(s.to_string());
+        }
+        s.chars()
+            .map(|c| {
+                // Unicodeのマイナス記号も`-`と同じく変換する
+                let c = if c == '−' { '-' } else { c };
+                let &(_, sub, sup) = Script::CHARS.iter().find(|(normal, _, _)| *normal == c)?;
+                match self {
+                    Script::Subscript(_) => Some(sub),
+                    _ => Some(sup),
+                }
+            })
+            .collect()
+    }
+
+    /// 上付き・下付き文字を通常の文字に戻す。対応する文字でなければ`None`
+    pub(crate) fn restore(&self, c: char) -> Option<char> {
+        Script::CHARS
+            .iter()
+            .find(|&&(_, sub, sup)| match self {
+                Script::Normal | Script::Overline => false,
+                Script::Subscript(_) => sub == c,
+                Script::Superscript(_) => sup == c,
+            })
+            .map(|(normal, _, _)| *normal)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct BinaryOpPrinter {
-    token: Token,
-    prefix: String,
-    infix: String,
-    suffix: String,
-    precedence: i32,
+    pub(crate) token: Token,
+    pub(crate) prefix: String,
+    pub(crate) infix: String,
+    pub(crate) suffix: String,
+    pub(crate) precedence: i32,
     /// 左結合性（A * B * C == (A * B) * C）
-    left_associative: bool,
-    /// 右結合性（A * B * C == A * (B * C)）
-    right_associative: bool,
+    pub(crate) left_associative: bool,
+    /// 右結合性（A * B * C == A * (B * C)）。
+    /// 同じ優先度の別の二項演算子の式が右の項の場合は、それが逆演算（`Printer::with_properties`）の場合のみ括弧を省略する
+    pub(crate) right_associative: bool,
     /// 左の項に括弧が必要かどうか
-    paren_left: bool,
+    pub(crate) paren_left: bool,
     /// 右の項に括弧が必要かどうか
-    paren_right: bool,
+    pub(crate) paren_right: bool,
+    /// 左の項の表示方法
+    pub(crate) left_script: Script,
+    /// 右の項の表示方法
+    pub(crate) right_script: Script,
+    /// 左右の項を入れ替えて表示するかどうか（`ⁿ√x`を`root(x, n)`と表示する場合など）。
+    /// 入れ替えた場合、`paren_left`などの左右の指定は表示上の位置に対して適用する
+    pub(crate) swapped: bool,
 }
 
 impl BinaryOpPrinter {
@@ -69,8 +288,24 @@ impl BinaryOpPrinter {
             right_associative,
             paren_left,
             paren_right,
+            left_script: Script::Normal,
+            right_script: Script::Normal,
+            swapped: false,
         }
     }
+
+    /// 左右の項を上付き・下付き文字で表示するよう指定する
+    pub fn scripts(mut self, left_script: Script, right_script: Script) -> BinaryOpPrinter {
+        self.left_script = left_script;
+        self.right_script = right_script;
+        self
+    }
+
+    /// 左右の項を入れ替えて表示するよう指定する
+    pub fn swapped(mut self) -> BinaryOpPrinter {
+        self.swapped = true;
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -79,6 +314,21 @@ pub struct Printer {
     binary_ops: Vec<BinaryOpPrinter>,
     paren_left: String,
     paren_right: String,
+    /// 結合的な二項演算子のトークン
+    associative: Vec<Token>,
+    /// 結合的な二項演算子と、その逆演算のトークンの組
+    inverses: Vec<(Token, Token)>,
+    /// 単項演算子と、それを左の項に移せる二項演算子のトークンの組
+    moves_into: Vec<(Token, Token)>,
+    /// 項を縦に積んで表示する（`\frac{a}{b}`）二項演算子のトークンと、その本来の優先度の組
+    stacked: Vec<(Token, i32)>,
+    parenthesization: Parenthesization,
+    /// 数値の前後に付ける文字列（MathMLの`<mn>`など）
+    number_prefix: String,
+    number_suffix: String,
+    /// 式全体の前後に付ける文字列（MathMLの`<math>`など）
+    enclosing_prefix: String,
+    enclosing_suffix: String,
 }
 
 impl Printer {
@@ -93,73 +343,380 @@ impl Printer {
             binary_ops,
             paren_left: paren_left.into(),
             paren_right: paren_right.into(),
+            associative: vec![],
+            inverses: vec![],
+            moves_into: vec![],
+            stacked: vec![],
+            parenthesization: Parenthesization::default(),
+            number_prefix: String::new(),
+            number_suffix: String::new(),
+            enclosing_prefix: String::new(),
+            enclosing_suffix: String::new(),
         }
     }
 
-    pub fn print(&self, equation: &Equation) -> String {
-        // 部分式文字列と、最後に追加された演算子の優先度のペアのスタック
-        let mut stack = Vec::<(String, i32)>::new();
+    /// 数値の前後に付ける文字列を指定する
+    pub fn numbers(mut self, prefix: impl Into<String>, suffix: impl Into<String>) -> Printer {
+        self.number_prefix = prefix.into();
+        self.number_suffix = suffix.into();
+        self
+    }
 
-        for token in equation.tokens.iter() {
-            match token {
-                0xe0..=0xef => {
-                    // 数値一桁目
-                    let n = token - 0xe0;
-                    stack.push((n.to_string(), 0));
-                }
-                0xf0..=0xff => {
-                    // 数値二桁目以降
-                    let n = token - 0xf0;
-                    let last_val = stack.pop().unwrap().0;
-                    stack.push((last_val + &n.to_string(), 0));
-                }
-                _ => {
-                    if let Some(op) = self.unary_ops.iter().find(|op| op.token == *token) {
-                        // 単項演算子
-                        let expr = stack.pop().unwrap();
-                        // 括弧が必要な場合は括弧をつける
-                        let expr = if op.paren && expr.1 >= op.precedence {
-                            self.paren_left.clone() + &expr.0 + &self.paren_right
-                        } else {
-                            expr.0.clone()
-                        };
-                        // prefix + expr + suffixをpush
-                        stack.push((op.prefix.clone() + &expr + &op.suffix, op.precedence));
-                    } else if let Some(op) = self.binary_ops.iter().find(|op| op.token == *token) {
-                        // 二項演算子
-                        let expr2 = stack.pop().unwrap();
-                        let expr1 = stack.pop().unwrap();
-                        // 括弧が必要な場合は括弧をつける（左側オペランド）
-                        let expr1 = if op.paren_left
-                            && (expr1.1 > op.precedence
-                                || expr1.1 == op.precedence && !op.left_associative)
-                        {
-                            self.paren_left.clone() + &expr1.0 + &self.paren_right
-                        } else {
-                            expr1.0.clone()
-                        };
-                        // 括弧が必要な場合は括弧をつける（右側オペランド）
-                        let expr2 = if op.paren_right
-                            && (expr2.1 > op.precedence
-                                || expr2.1 == op.precedence && !op.right_associative)
-                        {
-                            self.paren_left.clone() + &expr2.0 + &self.paren_right
-                        } else {
-                            expr2.0.clone()
-                        };
-                        // prefix + expr1 + infix + expr2 + suffixをpush
-                        stack.push((
-                            op.prefix.clone() + &expr1 + &op.infix + &expr2 + &op.suffix,
-                            op.precedence,
-                        ));
+    /// 括弧の付け方を指定する
+    pub fn parenthesization(mut self, parenthesization: Parenthesization) -> Printer {
+        self.parenthesization = parenthesization;
+        self
+    }
+
+    /// 項を縦に積んで表示する二項演算子のトークンと、その本来の優先度を指定する。
+    /// それ自体で範囲が明らかな表示（`\frac{a}{b}`）でも、右に続く指数や後置の単項演算子の項では括弧で囲む
+    /// （`\frac{4}{4}^{4}`では指数が分母だけに付くように見える）
+    pub fn stacked(mut self, stacked: Vec<(Token, i32)>) -> Printer {
+        self.stacked = stacked;
+        self
+    }
+
+    /// 式全体の前後に付ける文字列を指定する
+    pub fn enclose(mut self, prefix: impl Into<String>, suffix: impl Into<String>) -> Printer {
+        self.enclosing_prefix = prefix.into();
+        self.enclosing_suffix = suffix.into();
+        self
+    }
+
+    /// 演算子の代数的性質を表示に反映する。
+    /// 結合的な演算子が連続する場合は、結合性の指定によらず括弧を省略する
+    /// （右結合の指定のない`&`でも、`4&(4&4)`ではなく`4&4&4`とする）。
+    /// また、右結合の指定のある演算子の右の項が逆演算の式の場合（`4+(4-4)`）や、
+    /// `Semantic`の括弧の付け方で逆演算を連ねる場合や、単項演算子を左の項に移す場合の括弧の省略にも使う
+    pub fn with_properties(mut self, unary_ops: &[UnaryOp], binary_ops: &[BinaryOp]) -> Printer {
+        self.associative = binary_ops
+            .iter()
+            .filter(|op| op.associative)
+            .map(|op| op.token)
+            .collect();
+        self.inverses = binary_ops
+            .iter()
+            .filter(|op| op.associative)
+            .filter_map(|op| Some((op.token, op.inverse?)))
+            .collect();
+        self.moves_into = unary_ops
+            .iter()
+            .flat_map(|op| op.moves_into.iter().map(|token| (op.token, *token)))
+            .collect();
+        self
+    }
+
+    /// 演算子の一覧のすべての演算子に、過不足なく1つずつ表示方法があることを確かめる。
+    /// `Registry`は作った`Printer`をこれで確かめる
+    pub fn validate(
+        self,
+        unary_ops: &[UnaryOp],
+        binary_ops: &[BinaryOp],
+    ) -> Result<Printer, PrintError> {
+        let printer_tokens = self
+            .unary_ops
+            .iter()
+            .map(|op| (op.token, true))
+            .chain(self.binary_ops.iter().map(|op| (op.token, false)))
+            .collect::<Vec<_>>();
+        for (i, &(token, unary)) in printer_tokens.iter().enumerate() {
+            if token >= DECIMAL_POINT {
+                return Err(PrintError::ReservedToken(token));
+            }
+            if printer_tokens[..i].iter().any(|(t, _)| *t == token) {
+                return Err(PrintError::DuplicateOperator(token));
+            }
+            let known = if unary {
+                unary_ops.iter().any(|op| op.token == token)
+            } else {
+                binary_ops.iter().any(|op| op.token == token)
+            };
+            if !known {
+                return Err(PrintError::UnknownOperator(token));
+            }
+        }
+        let missing = unary_ops
+            .iter()
+            .map(|op| op.token)
+            .find(|token| self.unary_op(*token).is_none())
+            .or_else(|| {
+                binary_ops
+                    .iter()
+                    .map(|op| op.token)
+                    .find(|token| self.binary_op(*token).is_none())
+            });
+        match missing {
+            Some(token) => Err(PrintError::MissingOperator(token)),
+            None => Ok(self),
+        }
+    }
+
+    /// `token`の演算子の項が同じ結合的な演算子の式で、括弧を省略できるかどうか
+    fn is_associative_chain(&self, token: Token, operand: Option<Token>) -> bool {
+        operand == Some(token) && self.associative.contains(&token)
+    }
+
+    /// トークンに対応する単項演算子の表示方法
+    pub(crate) fn unary_op(&self, token: Token) -> Option<&UnaryOpPrinter> {
+        self.unary_ops.iter().find(|op| op.token == token)
+    }
+
+    /// トークンに対応する二項演算子の表示方法
+    pub(crate) fn binary_op(&self, token: Token) -> Option<&BinaryOpPrinter> {
+        self.binary_ops.iter().find(|op| op.token == token)
+    }
+
+    /// すべての項を括弧で囲む指定で、優先度が`precedence`の部分式を括弧で囲むかどうか。
+    /// 数値や関数呼び出しの形の式など、優先度が0の項は囲まない
+    fn fully_parenthesized(&self, precedence: i32) -> bool {
+        self.parenthesization == Parenthesization::Full && precedence > 0
+    }
+
+    /// 部分式（優先度が`precedence`で、最後の演算子が`last`）に右から続く演算子に対する優先度。
+    /// 項を縦に積んで表示する式は、それ自体で範囲が明らかでも本来の優先度とする
+    fn right_edge(&self, precedence: i32, last: Option<Token>) -> i32 {
+        match last {
+            Some(last) if precedence == 0 => self
+                .stacked
+                .iter()
+                .find(|(token, _)| *token == last)
+                .map_or(precedence, |(_, precedence)| *precedence),
+            _ => precedence,
+        }
+    }
+
+    /// 単項演算子の項（優先度が`precedence`で、最後の演算子が`last`の部分式）に括弧が必要かどうか
+    pub(crate) fn unary_paren(
+        &self,
+        op: &UnaryOpPrinter,
+        precedence: i32,
+        last: Option<Token>,
+    ) -> bool {
+        // 後置の演算子は項の右端に続く
+        let edge = if op.prefix.is_empty() {
+            self.right_edge(precedence, last)
+        } else {
+            precedence
+        };
+        op.paren
+            && (edge >= op.precedence || self.fully_parenthesized(precedence))
+            && !self.is_moved_into(op, precedence, last)
+    }
+
+    /// 単項演算子を適用した式の優先度と最後の演算子。
+    /// 単項演算子を項の左の項に移して表示する（`-4*4`）なら、項の優先度と演算子のまま
+    pub(crate) fn unary_result(
+        &self,
+        op: &UnaryOpPrinter,
+        precedence: i32,
+        last: Option<Token>,
+    ) -> (i32, Option<Token>) {
+        if self.is_moved_into(op, precedence, last) {
+            (precedence, last)
+        } else {
+            (op.precedence, Some(op.token))
+        }
+    }
+
+    /// 括弧の付け方が`Semantic`で、前置の単項演算子を、項（優先度が`precedence`で、最後の演算子が`last`の部分式）の
+    /// 左端に移して括弧を省けるかどうか。`f(a ∘ b) == f(a) ∘ b`なので`-(4*4)`を`-4*4`と表示しても値は変わらない。
+    /// 読み戻すときに単項演算子が項の左端の部分式だけに適用されるよう、
+    /// 単項演算子より弱く`last`より強くない演算子は、いずれも単項演算子を左の項に移せる二項演算子でなければならない
+    fn is_moved_into(&self, op: &UnaryOpPrinter, precedence: i32, last: Option<Token>) -> bool {
+        let movable = |token| {
+            self.moves_into.contains(&(op.token, token))
+                && self
+                    .binary_op(token)
+                    .is_some_and(|op| op.prefix.is_empty() && !op.swapped)
+        };
+        let between = |p: i32| op.precedence < p && p <= precedence;
+        self.parenthesization == Parenthesization::Semantic
+            && op.suffix.is_empty()
+            && op.script == Script::Normal
+            && op.precedence < precedence
+            && last.is_some_and(movable)
+            && !self.unary_ops.iter().any(|u| between(u.precedence))
+            && self
+                .binary_ops
+                .iter()
+                .all(|b| !between(b.precedence) || movable(b.token))
+    }
+
+    /// 二項演算子の表示上の左の項（優先度が`precedence`で、最後の演算子が`last`の部分式）に括弧が必要かどうか
+    pub(crate) fn left_paren(
+        &self,
+        op: &BinaryOpPrinter,
+        precedence: i32,
+        last: Option<Token>,
+    ) -> bool {
+        // 表示上の左の項には、右に演算子が続く
+        let edge = self.right_edge(precedence, last);
+        op.paren_left
+            && (edge > op.precedence
+                || edge == op.precedence
+                    && !op.left_associative
+                    && !self.is_associative_chain(op.token, last)
+                || self.fully_parenthesized(precedence))
+    }
+
+    /// 二項演算子の表示上の右の項に括弧が必要かどうか
+    pub(crate) fn right_paren(
+        &self,
+        op: &BinaryOpPrinter,
+        precedence: i32,
+        last: Option<Token>,
+    ) -> bool {
+        op.paren_right
+            && (precedence > op.precedence
+                || precedence == op.precedence
+                    && !self.is_right_associative_with(op, last)
+                    && !self.is_associative_chain(op.token, last)
+                    && !self.is_inverse_chain(op.token, last)
+                || self.fully_parenthesized(precedence))
+    }
+
+    /// 二項演算子の右の項の括弧を、右結合性の指定に従って省略してよいかどうか。
+    /// 右の項が同じ演算子か、その逆演算の式（`4+(4-4)`, `4*(4/4)`）なら`a ∘ (b ∙ c) == (a ∘ b) ∙ c`となる。
+    /// 同じ優先度でも`mod`などの別の二項演算子の式では値が変わるので省略しない
+    fn is_right_associative_with(&self, op: &BinaryOpPrinter, operand: Option<Token>) -> bool {
+        op.right_associative
+            && operand.is_none_or(|operand| {
+                operand == op.token
+                    || self.binary_op(operand).is_none()
+                    || self.inverses.contains(&(op.token, operand))
+            })
+    }
+
+    /// 括弧の付け方が`Semantic`で、`token`の結合的な演算子の右の項がその逆演算の式かどうか。
+    /// `a ∘ (b ∙ c) == (a ∘ b) ∙ c`なので、どちらも左結合で表示するなら括弧を省略できる
+    fn is_inverse_chain(&self, token: Token, operand: Option<Token>) -> bool {
+        let left_associative = |token| self.binary_op(token).is_some_and(|op| op.left_associative);
+        self.parenthesization == Parenthesization::Semantic
+            && operand.is_some_and(|operand| {
+                self.inverses.contains(&(token, operand))
+                    && left_associative(token)
+                    && left_associative(operand)
+            })
+    }
+
+    /// 単項演算子の表示方法の一覧
+    pub(crate) fn unary_ops(&self) -> &[UnaryOpPrinter] {
+        &self.unary_ops
+    }
+
+    /// 二項演算子の表示方法の一覧
+    pub(crate) fn binary_ops(&self) -> &[BinaryOpPrinter] {
+        &self.binary_ops
+    }
+
+    /// 括弧の文字列
+    pub(crate) fn parens(&self) -> (&str, &str) {
+        (&self.paren_left, &self.paren_right)
+    }
+
+    /// 演算子の項を表示する。`paren`は括弧が必要かどうか
+    fn script(&self, script: &Script, expr: &(String, i32, Option<Token>), paren: bool) -> String {
+        let wrap = |s: &str| self.paren_left.clone() + s + &self.paren_right;
+        let text = if paren { wrap(&expr.0) } else { expr.0.clone() };
+        match script {
+            Script::Normal => text,
+            Script::Overline if paren => {
+                // 結合文字はそれ自体には上線を付けない
+                expr.0.chars().fold(String::new(), |mut s, c| {
+                    s.push(c);
+                    if !('\u{0300}'..='\u{036f}').contains(&c) {
+                        s.push('\u{0305}');
+                    }
+                    s
+                })
+            }
+            Script::Overline => text,
+            Script::Subscript(fallback) | Script::Superscript(fallback) => {
+                script.convert(&text).unwrap_or_else(|| {
+                    // 変換できない場合は記号に続けて表示する。境界が曖昧にならないよう、数値以外は括弧で囲む
+                    if paren || expr.2.is_none() {
+                        fallback.clone() + &text
                     } else {
-                        panic!("Unexpected token appeared: {}", token);
+                        fallback.clone() + &wrap(&text)
                     }
-                }
+                })
             }
         }
+    }
+
+    pub fn print(&self, equation: &Equation) -> Result<String, PrintError> {
+        let expr = postfix::walk(&equation.tokens, self)?;
+        Ok(self.enclosing_prefix.clone() + &expr.0 + &self.enclosing_suffix)
+    }
+}
+
+/// 部分式文字列と、最後に適用した演算子の優先度およびトークンの組を組み立てる
+impl Visitor for Printer {
+    type Output = (String, i32, Option<Token>);
+    type Error = PrintError;
 
-        assert!(stack.len() == 1);
-        stack[0].0.to_string()
+    fn arity(&self, token: Token) -> Option<Arity> {
+        if self.unary_op(token).is_some() {
+            Some(Arity::Unary)
+        } else if self.binary_op(token).is_some() {
+            Some(Arity::Binary)
+        } else {
+            None
+        }
+    }
+
+    fn number(&self, digits: &str, decimal: bool) -> Result<Self::Output, PrintError> {
+        let point = if decimal { "." } else { "" };
+        Ok((
+            self.number_prefix.clone() + point + digits + &self.number_suffix,
+            0,
+            None,
+        ))
+    }
+
+    fn unary(
+        &self,
+        position: usize,
+        token: Token,
+        expr: Self::Output,
+    ) -> Result<Self::Output, PrintError> {
+        let op = self
+            .unary_op(token)
+            .ok_or(PrintError::UnknownToken(position, token))?;
+        // 括弧が必要な場合は括弧をつける
+        let paren = self.unary_paren(op, expr.1, expr.2);
+        let (precedence, last) = self.unary_result(op, expr.1, expr.2);
+        let expr = self.script(&op.script, &expr, paren);
+        // prefix + expr + suffix
+        Ok((op.prefix.clone() + &expr + &op.suffix, precedence, last))
+    }
+
+    fn binary(
+        &self,
+        position: usize,
+        token: Token,
+        expr1: Self::Output,
+        expr2: Self::Output,
+    ) -> Result<Self::Output, PrintError> {
+        let op = self
+            .binary_op(token)
+            .ok_or(PrintError::UnknownToken(position, token))?;
+        let (expr1, expr2) = if op.swapped {
+            (expr2, expr1)
+        } else {
+            (expr1, expr2)
+        };
+        // 括弧が必要な場合は括弧をつける（左側オペランド）
+        let paren1 = self.left_paren(op, expr1.1, expr1.2);
+        let expr1 = self.script(&op.left_script, &expr1, paren1);
+        // 括弧が必要な場合は括弧をつける（右側オペランド）
+        let paren2 = self.right_paren(op, expr2.1, expr2.2);
+        let expr2 = self.script(&op.right_script, &expr2, paren2);
+        // prefix + expr1 + infix + expr2 + suffix
+        Ok((
+            op.prefix.clone() + &expr1 + &op.infix + &expr2 + &op.suffix,
+            op.precedence,
+            Some(op.token),
+        ))
     }
 }
@@ -0,0 +1,327 @@
+//! 分数を縦に積み、根号の上に線を引き、指数を上げた複数行の表示
+//!
+//! ```text
+//!      _
+//!  4+\/4
+//! -------
+//!    4
+//! ```
+
+use super::{Parenthesization, PrintError, Printer, Script};
+use crate::{
+    postfix::{self, Arity, Visitor},
+    search::{Equation, Token},
+};
+use std::{collections::HashMap, fmt};
+
+/// 演算子の二次元的な配置。左右の項はトークン列での順（表示上の順ではない）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Layout {
+    /// 分数。左の項を分子、右の項を分母として縦に積む
+    Fraction,
+    /// 累乗。右の項を指数として左の項の右上に上げる
+    Power,
+    /// 根号。単項演算子なら項を根号の中に入れる。二項演算子なら右の項を根号の中に入れ、左の項を指数にする
+    Root,
+    /// 指数の決まった根号（`∛x`）
+    IndexedRoot(String),
+}
+
+/// 文字を並べた長方形の領域
+#[derive(Debug, Clone)]
+struct Block {
+    lines: Vec<Vec<char>>,
+    width: usize,
+    /// 前後の式と高さを揃える行。上付き・下付きの項では領域の外の行を指す
+    baseline: isize,
+}
+
+impl Block {
+    fn text(s: &str) -> Block {
+        let line = s.chars().collect::<Vec<_>>();
+        Block {
+            width: line.len(),
+            lines: vec![line],
+            baseline: 0,
+        }
+    }
+
+    fn height(&self) -> isize {
+        self.lines.len() as isize
+    }
+
+    /// 領域を指定した位置に重ねて置く。位置は、基準の行を0とした領域の先頭行と、列の組。
+    /// 空白は下の文字を消さない
+    fn compose(parts: &[(isize, usize, &Block)]) -> Block {
+        let top = parts.iter().map(|(row, _, _)| *row).min().unwrap_or(0);
+        let bottom = parts
+            .iter()
+            .map(|(row, _, b)| row + b.height())
+            .max()
+            .unwrap_or(0);
+        let width = parts
+            .iter()
+            .map(|(_, col, b)| col + b.width)
+            .max()
+            .unwrap_or(0);
+        let mut lines = vec![vec![' '; width]; (bottom - top) as usize];
+        for (row, col, block) in parts {
+            for (i, line) in block.lines.iter().enumerate() {
+                let canvas = &mut lines[(row - top) as usize + i];
+                for (j, c) in line.iter().enumerate() {
+                    if *c != ' ' {
+                        canvas[col + j] = *c;
+                    }
+                }
+            }
+        }
+        Block {
+            lines,
+            width,
+            baseline: -top,
+        }
+    }
+
+    /// 基準の行を揃えて横に並べる
+    fn row(blocks: &[Block]) -> Block {
+        let mut col = 0;
+        let parts = blocks
+            .iter()
+            .map(|b| {
+                let part = (-b.baseline, col, b);
+                col += b.width;
+                part
+            })
+            .collect::<Vec<_>>();
+        Block::compose(&parts)
+    }
+
+    /// 括弧で囲む。複数行にわたる場合は高さに合わせた括弧にする
+    fn parenthesize(&self) -> Block {
+        let (left, right) = if self.lines.len() == 1 {
+            (Block::text("("), Block::text(")"))
+        } else {
+            let column = |top: char, middle: char, bottom: char| {
+                let last = self.lines.len() - 1;
+                let lines = (0..=last)
+                    .map(|i| match i {
+                        0 => vec![top],
+                        _ if i == last => vec![bottom],
+                        _ => vec![middle],
+                    })
+                    .collect();
+                Block {
+                    lines,
+                    width: 1,
+                    baseline: self.baseline,
+                }
+            };
+            (column('/', '|', '\\'), column('\\', '|', '/'))
+        };
+        Block::row(&[left, self.clone(), right])
+    }
+
+    /// 上に線を引く
+    fn overline(&self) -> Block {
+        let bar = Block::text(&"_".repeat(self.width));
+        Block::compose(&[(-self.baseline - 1, 0, &bar), (-self.baseline, 0, self)])
+    }
+
+    /// 分数
+    fn fraction(numerator: &Block, denominator: &Block) -> Block {
+        let width = numerator.width.max(denominator.width) + 2;
+        let bar = Block::text(&"-".repeat(width));
+        Block::compose(&[
+            (
+                -numerator.height(),
+                (width - numerator.width) / 2,
+                numerator,
+            ),
+            (0, 0, &bar),
+            (1, (width - denominator.width) / 2, denominator),
+        ])
+    }
+
+    /// 累乗
+    fn power(base: &Block, exponent: &Block) -> Block {
+        Block::compose(&[
+            (-base.baseline, 0, base),
+            (-base.baseline - exponent.height(), base.width, exponent),
+        ])
+    }
+
+    /// 根号。指数があれば左上に置く
+    fn root(index: Option<&Block>, radicand: &Block) -> Block {
+        let height = radicand.lines.len();
+        let top = -radicand.baseline;
+        // 指数は根号の`\`の上に置く
+        let offset = index.map_or(0, |b| b.width.saturating_sub(1));
+        let slash = Block::text("/");
+        let backslash = Block::text("\\");
+        let bar = Block::text(&"_".repeat(radicand.width));
+        let mut parts = vec![
+            (top + height as isize - 1, offset, &backslash),
+            (top - 1, offset + height + 1, &bar),
+            (top, offset + height + 1, radicand),
+        ];
+        for i in 0..height {
+            parts.push((top + i as isize, offset + height - i, &slash));
+        }
+        if let Some(index) = index {
+            parts.push((top + height as isize - 1 - index.height(), 0, index));
+        }
+        Block::compose(&parts)
+    }
+
+    /// 上付き・下付きなどの指定に従って項を配置する
+    fn script(&self, script: &Script, paren: bool) -> Block {
+        let block = if paren && *script != Script::Overline {
+            self.parenthesize()
+        } else {
+            self.clone()
+        };
+        match script {
+            Script::Normal => block,
+            Script::Overline if paren => block.overline(),
+            Script::Overline => block,
+            Script::Subscript(_) => Block::compose(&[(1, 0, &block)]),
+            Script::Superscript(_) => Block::compose(&[(-block.height(), 0, &block)]),
+        }
+    }
+}
+
+impl fmt::Display for Block {
+    /// 行末の空白を除いて行を並べる
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines = self
+            .lines
+            .iter()
+            .map(|line| line.iter().collect::<String>().trim_end().to_string())
+            .collect::<Vec<_>>();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// 複数行で式を表示する。二次元的な配置のない演算子は`Printer`の表示方法で1行に並べる
+#[derive(Debug, Clone)]
+pub struct PrettyPrinter {
+    printer: Printer,
+    /// 演算子のトークンと二次元的な配置
+    layouts: HashMap<Token, Layout>,
+}
+
+impl PrettyPrinter {
+    pub fn new(printer: Printer, layouts: HashMap<Token, Layout>) -> PrettyPrinter {
+        PrettyPrinter { printer, layouts }
+    }
+
+    /// 1行に並べる部分の括弧の付け方を指定する
+    pub fn parenthesization(mut self, parenthesization: Parenthesization) -> PrettyPrinter {
+        self.printer = self.printer.parenthesization(parenthesization);
+        self
+    }
+
+    pub fn print(&self, equation: &Equation) -> Result<String, PrintError> {
+        Ok(self.render(equation)?.to_string())
+    }
+
+    /// 式の前に、基準の行を揃えてラベル（`8 = `など）を付けて表示する
+    pub fn print_with_label(&self, label: &str, equation: &Equation) -> Result<String, PrintError> {
+        Ok(Block::row(&[Block::text(label), self.render(equation)?]).to_string())
+    }
+
+    fn render(&self, equation: &Equation) -> Result<Block, PrintError> {
+        Ok(postfix::walk(&equation.tokens, &Renderer(self))?.0)
+    }
+}
+
+/// 部分式の領域と、最後に適用した演算子の優先度およびトークンの組を組み立てる
+struct Renderer<'a>(&'a PrettyPrinter);
+
+impl Visitor for Renderer<'_> {
+    type Output = (Block, i32, Option<Token>);
+    type Error = PrintError;
+
+    fn arity(&self, token: Token) -> Option<Arity> {
+        self.0.printer.arity(token)
+    }
+
+    fn number(&self, digits: &str, decimal: bool) -> Result<Self::Output, PrintError> {
+        let point = if decimal { "." } else { "" };
+        Ok((Block::text(&(point.to_string() + digits)), 0, None))
+    }
+
+    fn unary(
+        &self,
+        position: usize,
+        token: Token,
+        expr: Self::Output,
+    ) -> Result<Self::Output, PrintError> {
+        let PrettyPrinter { printer, layouts } = self.0;
+        let op = printer
+            .unary_op(token)
+            .ok_or(PrintError::UnknownToken(position, token))?;
+        let block = match layouts.get(&token) {
+            Some(Layout::Root) => Block::root(None, &expr.0),
+            Some(Layout::IndexedRoot(index)) => Block::root(Some(&Block::text(index)), &expr.0),
+            _ => {
+                let paren = printer.unary_paren(op, expr.1, expr.2);
+                Block::row(&[
+                    Block::text(&op.prefix),
+                    expr.0.script(&op.script, paren),
+                    Block::text(&op.suffix),
+                ])
+            }
+        };
+        // 根号は線で範囲が明らかなので、項として括弧を必要としない
+        let (precedence, last) = match layouts.get(&token) {
+            Some(Layout::Root | Layout::IndexedRoot(_)) => (0, Some(op.token)),
+            _ => printer.unary_result(op, expr.1, expr.2),
+        };
+        Ok((block, precedence, last))
+    }
+
+    fn binary(
+        &self,
+        position: usize,
+        token: Token,
+        expr1: Self::Output,
+        expr2: Self::Output,
+    ) -> Result<Self::Output, PrintError> {
+        let PrettyPrinter { printer, layouts } = self.0;
+        let op = printer
+            .binary_op(token)
+            .ok_or(PrintError::UnknownToken(position, token))?;
+        let (block, precedence) = match layouts.get(&token) {
+            Some(Layout::Fraction) => (Block::fraction(&expr1.0, &expr2.0), op.precedence),
+            Some(Layout::Power) => {
+                let paren = printer.left_paren(op, expr1.1, expr1.2);
+                let base = if paren {
+                    expr1.0.parenthesize()
+                } else {
+                    expr1.0
+                };
+                (Block::power(&base, &expr2.0), op.precedence)
+            }
+            Some(Layout::Root) => (Block::root(Some(&expr1.0), &expr2.0), 0),
+            _ => {
+                let (expr1, expr2) = if op.swapped {
+                    (expr2, expr1)
+                } else {
+                    (expr1, expr2)
+                };
+                let paren1 = printer.left_paren(op, expr1.1, expr1.2);
+                let paren2 = printer.right_paren(op, expr2.1, expr2.2);
+                let block = Block::row(&[
+                    Block::text(&op.prefix),
+                    expr1.0.script(&op.left_script, paren1),
+                    Block::text(&op.infix),
+                    expr2.0.script(&op.right_script, paren2),
+                    Block::text(&op.suffix),
+                ]);
+                (block, op.precedence)
+            }
+        };
+        Ok((block, precedence, Some(op.token)))
+    }
+}
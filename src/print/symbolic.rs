@@ -0,0 +1,94 @@
+//! 演算子の記号（または名前）を使った、括弧や優先順位によらない表示
+
+use super::PrintError;
+use crate::{
+    postfix::{self, Arity, Visitor},
+    search::{Equation, Token},
+};
+use std::collections::HashMap;
+
+/// 式の表記法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolicNotation {
+    /// 逆ポーランド記法（`4 4 * 4 4 / +`）
+    Rpn,
+    /// S式（`(+ (* 4 4) (/ 4 4))`）
+    SExpression,
+}
+
+#[derive(Debug, Clone)]
+pub struct SymbolicPrinter {
+    notation: SymbolicNotation,
+    /// 単項演算子のトークンと記号
+    unary_ops: HashMap<Token, String>,
+    /// 二項演算子のトークンと記号
+    binary_ops: HashMap<Token, String>,
+}
+
+impl SymbolicPrinter {
+    pub fn new(
+        notation: SymbolicNotation,
+        unary_ops: HashMap<Token, String>,
+        binary_ops: HashMap<Token, String>,
+    ) -> SymbolicPrinter {
+        SymbolicPrinter {
+            notation,
+            unary_ops,
+            binary_ops,
+        }
+    }
+
+    pub fn print(&self, equation: &Equation) -> Result<String, PrintError> {
+        postfix::walk(&equation.tokens, self)
+    }
+
+    /// 演算子を項に適用した式
+    fn apply(&self, symbol: &str, operands: &[String]) -> String {
+        match self.notation {
+            SymbolicNotation::Rpn => operands.join(" ") + " " + symbol,
+            SymbolicNotation::SExpression => format!("({} {})", symbol, operands.join(" ")),
+        }
+    }
+}
+
+/// 部分式文字列を組み立てる
+impl Visitor for SymbolicPrinter {
+    type Output = String;
+    type Error = PrintError;
+
+    fn arity(&self, token: Token) -> Option<Arity> {
+        if self.unary_ops.contains_key(&token) {
+            Some(Arity::Unary)
+        } else if self.binary_ops.contains_key(&token) {
+            Some(Arity::Binary)
+        } else {
+            None
+        }
+    }
+
+    fn number(&self, digits: &str, decimal: bool) -> Result<String, PrintError> {
+        Ok(if decimal { "." } else { "" }.to_string() + digits)
+    }
+
+    fn unary(&self, position: usize, token: Token, expr: String) -> Result<String, PrintError> {
+        let symbol = self
+            .unary_ops
+            .get(&token)
+            .ok_or(PrintError::UnknownToken(position, token))?;
+        Ok(self.apply(symbol, &[expr]))
+    }
+
+    fn binary(
+        &self,
+        position: usize,
+        token: Token,
+        expr1: String,
+        expr2: String,
+    ) -> Result<String, PrintError> {
+        let symbol = self
+            .binary_ops
+            .get(&token)
+            .ok_or(PrintError::UnknownToken(position, token))?;
+        Ok(self.apply(symbol, &[expr1, expr2]))
+    }
+}
@@ -0,0 +1,1195 @@
+pub mod catalogue;
+
+use crate::{
+    ast::AstBuilder,
+    evaluate::Evaluator,
+    parse::Parser,
+    print::{
+        pretty::{Layout, PrettyPrinter},
+        symbolic::{SymbolicNotation, SymbolicPrinter},
+        BinaryOpPrinter, PrintStyle, Printer, Script, UnaryOpPrinter,
+    },
+    search::{BinaryOp, Searcher, Token, UnaryOp, DECIMAL_POINT},
+};
+use std::{collections::HashMap, error, fmt};
+
+/// ユーザー定義の演算子に割り当てるトークンの範囲の先頭
+pub const USER_TOKEN_START: Token = 0x80;
+
+/// 単項演算子の定義。探索に使う演算と、その表示方法の組
+#[derive(Debug, Clone)]
+pub struct UnaryOpDef {
+    /// 演算子名
+    pub name: String,
+    pub op: UnaryOp,
+    pub printer: UnaryOpPrinter,
+    /// Unicodeのスタイルで表示する場合の表示方法。`None`なら`printer`を使う。
+    /// 優先度と結合性は`printer`のものを使う（`derive`）
+    pub unicode_printer: Option<UnaryOpPrinter>,
+    /// ASCII文字だけで表示する場合の表示方法。`None`なら`printer`がASCII文字だけならそれを、
+    /// そうでなければ演算子名を使った関数呼び出しの形（`name(x)`）を使う。優先度と結合性は`printer`のものを使う
+    pub ascii_printer: Option<UnaryOpPrinter>,
+    /// LaTeXで表示する場合の表示方法。`None`ならASCII文字だけで表示する場合の表示方法を使う。
+    /// 優先度と結合性は`printer`のものを使う
+    pub latex_printer: Option<UnaryOpPrinter>,
+    /// MathMLで表示する場合の表示方法。`None`なら通常の表示方法の記号を`<mo>`要素にして使う。
+    /// 優先度と結合性は`printer`のものを使う
+    pub mathml_printer: Option<UnaryOpPrinter>,
+    /// 逆ポーランド記法やS式で使う記号。`None`なら演算子名を使う
+    pub symbol: Option<String>,
+    /// 複数行で表示する場合の二次元的な配置。`None`なら1行に並べる
+    pub layout: Option<Layout>,
+}
+
+impl UnaryOpDef {
+    pub fn new(
+        name: impl Into<String>,
+        op: UnaryOp,
+        prefix: impl Into<String>,
+        suffix: impl Into<String>,
+        precedence: i32,
+        paren: bool,
+    ) -> UnaryOpDef {
+        let printer = UnaryOpPrinter::new(op.token, prefix, suffix, precedence, paren);
+        UnaryOpDef {
+            name: name.into(),
+            op,
+            printer,
+            unicode_printer: None,
+            ascii_printer: None,
+            latex_printer: None,
+            mathml_printer: None,
+            symbol: None,
+            layout: None,
+        }
+    }
+
+    /// Unicodeのスタイルで表示する場合の記号を指定する
+    pub fn unicode(
+        mut self,
+        prefix: impl Into<String>,
+        suffix: impl Into<String>,
+        paren: bool,
+        script: Script,
+    ) -> UnaryOpDef {
+        self.unicode_printer = Some(self.style(prefix, suffix, paren).script(script));
+        self
+    }
+
+    /// ASCII文字だけで表示する場合の記号を指定する
+    pub fn ascii(
+        mut self,
+        prefix: impl Into<String>,
+        suffix: impl Into<String>,
+        paren: bool,
+    ) -> UnaryOpDef {
+        self.ascii_printer = Some(self.style(prefix, suffix, paren));
+        self
+    }
+
+    /// スタイルごとの表示方法を作る。優先度は使うときに`derive`で決める
+    fn style(
+        &self,
+        prefix: impl Into<String>,
+        suffix: impl Into<String>,
+        paren: bool,
+    ) -> UnaryOpPrinter {
+        UnaryOpPrinter::new(self.op.token, prefix, suffix, 0, paren)
+    }
+
+    /// スタイルごとの表示方法に`printer`の優先度を当てはめる。
+    /// 記号で項を囲む関数呼び出しの形（`sqrt(x)`）は、それ自体で範囲が明らかなので優先度を0とする
+    fn derive(&self, p: &UnaryOpPrinter) -> UnaryOpPrinter {
+        let closed = !p.prefix.is_empty() && !p.suffix.is_empty() && !p.paren;
+        UnaryOpPrinter {
+            precedence: if closed { 0 } else { self.printer.precedence },
+            ..p.clone()
+        }
+    }
+
+    /// LaTeXで表示する場合の記号を指定する
+    pub fn latex(
+        mut self,
+        prefix: impl Into<String>,
+        suffix: impl Into<String>,
+        paren: bool,
+    ) -> UnaryOpDef {
+        self.latex_printer = Some(self.style(prefix, suffix, paren));
+        self
+    }
+
+    /// MathMLで表示する場合の記号を指定する
+    pub fn mathml(
+        mut self,
+        prefix: impl Into<String>,
+        suffix: impl Into<String>,
+        paren: bool,
+    ) -> UnaryOpDef {
+        self.mathml_printer = Some(self.style(prefix, suffix, paren));
+        self
+    }
+
+    /// 記号を変更する。UnicodeとASCIIのスタイル用の記号の指定は取り消し、それらのスタイルでも同じ記号を使う
+    /// （ASCII文字以外を含む場合、ASCIIのスタイルでは関数呼び出しの形になる）。LaTeXとMathMLは独自の記号を使い続ける
+    pub fn set_symbols(&mut self, prefix: impl Into<String>, suffix: impl Into<String>) {
+        self.printer.prefix = prefix.into();
+        self.printer.suffix = suffix.into();
+        self.unicode_printer = None;
+        self.ascii_printer = None;
+    }
+
+    /// すべてのスタイルの表示方法で、項に括弧が必要かどうかを変更する
+    pub fn set_paren(&mut self, paren: bool) {
+        for p in self.printers_mut() {
+            p.paren = paren;
+        }
+    }
+
+    /// 通常の表示方法と、指定されたスタイルごとの表示方法
+    fn printers_mut(&mut self) -> impl Iterator<Item = &mut UnaryOpPrinter> {
+        std::iter::once(&mut self.printer).chain(
+            [
+                &mut self.unicode_printer,
+                &mut self.ascii_printer,
+                &mut self.latex_printer,
+                &mut self.mathml_printer,
+            ]
+            .into_iter()
+            .flatten(),
+        )
+    }
+
+    /// 逆ポーランド記法やS式で使う記号を指定する
+    pub fn symbol(mut self, symbol: impl Into<String>) -> Self {
+        self.symbol = Some(symbol.into());
+        self
+    }
+
+    /// 複数行で表示する場合の二次元的な配置を指定する
+    pub fn layout(mut self, layout: Layout) -> Self {
+        self.layout = Some(layout);
+        self
+    }
+
+    /// 逆ポーランド記法やS式で使う記号
+    pub fn effective_symbol(&self) -> &str {
+        self.symbol.as_deref().unwrap_or(&self.name)
+    }
+
+    pub fn token(&self) -> Token {
+        self.op.token
+    }
+
+    /// 同じ表示になり、式を読み分けられないかどうか
+    fn is_ambiguous_with(&self, other: &UnaryOpDef) -> bool {
+        let same =
+            |p: &UnaryOpPrinter, q: &UnaryOpPrinter| p.prefix == q.prefix && p.suffix == q.suffix;
+        same(&self.printer, &other.printer)
+            || same(
+                &self.effective_unicode_printer(),
+                &other.effective_unicode_printer(),
+            )
+            || same(
+                &self.effective_ascii_printer(),
+                &other.effective_ascii_printer(),
+            )
+    }
+
+    /// Unicodeのスタイルで表示する場合に実際に使う表示方法
+    fn effective_unicode_printer(&self) -> UnaryOpPrinter {
+        match &self.unicode_printer {
+            Some(p) => self.derive(p),
+            None => self.printer.clone(),
+        }
+    }
+
+    /// ASCII文字だけで表示する場合に実際に使う表示方法
+    fn effective_ascii_printer(&self) -> UnaryOpPrinter {
+        match &self.ascii_printer {
+            Some(p) => self.derive(p),
+            None if (self.printer.prefix.clone() + &self.printer.suffix).is_ascii() => {
+                self.printer.clone().script(Script::Normal)
+            }
+            None => self.derive(&self.style(self.name.clone() + "(", ")", false)),
+        }
+    }
+
+    /// LaTeXで表示する場合に実際に使う表示方法
+    fn effective_latex_printer(&self) -> UnaryOpPrinter {
+        match &self.latex_printer {
+            Some(p) => self.derive(p),
+            None => self.effective_ascii_printer(),
+        }
+    }
+
+    /// MathMLで表示する場合に実際に使う表示方法
+    fn effective_mathml_printer(&self) -> UnaryOpPrinter {
+        let p = &self.printer;
+        match &self.mathml_printer {
+            Some(p) => self.derive(p),
+            None => {
+                UnaryOpPrinter::new(p.token, mo(&p.prefix), mo(&p.suffix), p.precedence, p.paren)
+            }
+        }
+    }
+}
+
+/// 二項演算子の定義。探索に使う演算と、その表示方法の組
+#[derive(Debug, Clone)]
+pub struct BinaryOpDef {
+    /// 演算子名
+    pub name: String,
+    pub op: BinaryOp,
+    pub printer: BinaryOpPrinter,
+    /// Unicodeのスタイルで表示する場合の表示方法。`None`なら`printer`を使う。
+    /// 優先度と結合性は`printer`のものを使う（`derive`）
+    pub unicode_printer: Option<BinaryOpPrinter>,
+    /// ASCII文字だけで表示する場合の表示方法。`None`なら`printer`がASCII文字だけならそれを、
+    /// そうでなければ演算子名を使った関数呼び出しの形（`name(x)`）を使う。優先度と結合性は`printer`のものを使う
+    pub ascii_printer: Option<BinaryOpPrinter>,
+    /// LaTeXで表示する場合の表示方法。`None`ならASCII文字だけで表示する場合の表示方法を使う。
+    /// 優先度と結合性は`printer`のものを使う
+    pub latex_printer: Option<BinaryOpPrinter>,
+    /// MathMLで表示する場合の表示方法。`None`なら通常の表示方法の記号を`<mo>`要素にして使う。
+    /// 優先度と結合性は`printer`のものを使う
+    pub mathml_printer: Option<BinaryOpPrinter>,
+    /// 逆ポーランド記法やS式で使う記号。`None`なら演算子名を使う
+    pub symbol: Option<String>,
+    /// 複数行で表示する場合の二次元的な配置。`None`なら1行に並べる
+    pub layout: Option<Layout>,
+}
+
+impl BinaryOpDef {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        name: impl Into<String>,
+        op: BinaryOp,
+        prefix: impl Into<String>,
+        infix: impl Into<String>,
+        suffix: impl Into<String>,
+        precedence: i32,
+        left_associative: bool,
+        right_associative: bool,
+        paren_left: bool,
+        paren_right: bool,
+    ) -> BinaryOpDef {
+        let printer = BinaryOpPrinter::new(
+            op.token,
+            prefix,
+            infix,
+            suffix,
+            precedence,
+            left_associative,
+            right_associative,
+            paren_left,
+            paren_right,
+        );
+        BinaryOpDef {
+            name: name.into(),
+            op,
+            printer,
+            unicode_printer: None,
+            ascii_printer: None,
+            latex_printer: None,
+            mathml_printer: None,
+            symbol: None,
+            layout: None,
+        }
+    }
+
+    /// 左右の項を上付き・下付き文字で表示するよう指定する（ASCII用の表示方法には影響しない）
+    pub fn scripts(mut self, left_script: Script, right_script: Script) -> BinaryOpDef {
+        self.printer = self.printer.scripts(left_script, right_script);
+        self
+    }
+
+    /// Unicodeのスタイルで表示する場合の記号を指定する。
+    /// 項の表示方法は`printer`のものを引き継ぐので、変える場合は続けて`unicode_scripts`を使う
+    pub fn unicode(
+        mut self,
+        prefix: impl Into<String>,
+        infix: impl Into<String>,
+        suffix: impl Into<String>,
+        paren_left: bool,
+        paren_right: bool,
+    ) -> BinaryOpDef {
+        let mut printer = self
+            .style(prefix, infix, suffix, paren_left, paren_right)
+            .scripts(
+                self.printer.left_script.clone(),
+                self.printer.right_script.clone(),
+            );
+        printer.swapped = self.printer.swapped;
+        self.unicode_printer = Some(printer);
+        self
+    }
+
+    /// Unicodeのスタイルで表示する場合の、左右の項の表示方法を指定する
+    pub fn unicode_scripts(mut self, left_script: Script, right_script: Script) -> BinaryOpDef {
+        let printer = self
+            .unicode_printer
+            .take()
+            .unwrap_or_else(|| self.printer.clone());
+        self.unicode_printer = Some(printer.scripts(left_script, right_script));
+        self
+    }
+
+    /// ASCII文字だけで表示する場合の記号を指定する
+    pub fn ascii(
+        mut self,
+        prefix: impl Into<String>,
+        infix: impl Into<String>,
+        suffix: impl Into<String>,
+        paren_left: bool,
+        paren_right: bool,
+    ) -> BinaryOpDef {
+        self.ascii_printer = Some(self.style(prefix, infix, suffix, paren_left, paren_right));
+        self
+    }
+
+    /// スタイルごとの表示方法を作る。優先度と結合性は使うときに`derive`で決める
+    fn style(
+        &self,
+        prefix: impl Into<String>,
+        infix: impl Into<String>,
+        suffix: impl Into<String>,
+        paren_left: bool,
+        paren_right: bool,
+    ) -> BinaryOpPrinter {
+        BinaryOpPrinter::new(
+            self.op.token,
+            prefix,
+            infix,
+            suffix,
+            0,
+            false,
+            false,
+            paren_left,
+            paren_right,
+        )
+    }
+
+    /// スタイルごとの表示方法に`printer`の優先度と結合性を当てはめる。
+    /// 記号で項を囲む関数呼び出しの形（`gcd(a, b)`）は、それ自体で範囲が明らかなので優先度を0とする
+    fn derive(&self, p: &BinaryOpPrinter) -> BinaryOpPrinter {
+        let closed =
+            !p.prefix.is_empty() && !p.suffix.is_empty() && !p.paren_left && !p.paren_right;
+        BinaryOpPrinter {
+            precedence: if closed { 0 } else { self.printer.precedence },
+            left_associative: self.printer.left_associative,
+            right_associative: self.printer.right_associative,
+            ..p.clone()
+        }
+    }
+
+    /// LaTeXで表示する場合の記号を指定する
+    pub fn latex(
+        mut self,
+        prefix: impl Into<String>,
+        infix: impl Into<String>,
+        suffix: impl Into<String>,
+        paren_left: bool,
+        paren_right: bool,
+    ) -> BinaryOpDef {
+        self.latex_printer = Some(self.style(prefix, infix, suffix, paren_left, paren_right));
+        self
+    }
+
+    /// MathMLで表示する場合の記号を指定する
+    pub fn mathml(
+        mut self,
+        prefix: impl Into<String>,
+        infix: impl Into<String>,
+        suffix: impl Into<String>,
+        paren_left: bool,
+        paren_right: bool,
+    ) -> BinaryOpDef {
+        self.mathml_printer = Some(self.style(prefix, infix, suffix, paren_left, paren_right));
+        self
+    }
+
+    /// 記号を変更する。UnicodeとASCIIのスタイル用の記号の指定は取り消し、それらのスタイルでも同じ記号を使う
+    /// （ASCII文字以外を含む場合、ASCIIのスタイルでは関数呼び出しの形になる）。LaTeXとMathMLは独自の記号を使い続ける
+    pub fn set_symbols(
+        &mut self,
+        prefix: impl Into<String>,
+        infix: impl Into<String>,
+        suffix: impl Into<String>,
+    ) {
+        self.printer.prefix = prefix.into();
+        self.printer.infix = infix.into();
+        self.printer.suffix = suffix.into();
+        self.unicode_printer = None;
+        self.ascii_printer = None;
+    }
+
+    /// すべてのスタイルの表示方法で、左の項に括弧が必要かどうかを変更する
+    pub fn set_paren_left(&mut self, paren: bool) {
+        for p in self.printers_mut() {
+            p.paren_left = paren;
+        }
+    }
+
+    /// すべてのスタイルの表示方法で、右の項に括弧が必要かどうかを変更する
+    pub fn set_paren_right(&mut self, paren: bool) {
+        for p in self.printers_mut() {
+            p.paren_right = paren;
+        }
+    }
+
+    /// 通常の表示方法と、指定されたスタイルごとの表示方法
+    fn printers_mut(&mut self) -> impl Iterator<Item = &mut BinaryOpPrinter> {
+        std::iter::once(&mut self.printer).chain(
+            [
+                &mut self.unicode_printer,
+                &mut self.ascii_printer,
+                &mut self.latex_printer,
+                &mut self.mathml_printer,
+            ]
+            .into_iter()
+            .flatten(),
+        )
+    }
+
+    /// 逆ポーランド記法やS式で使う記号を指定する
+    pub fn symbol(mut self, symbol: impl Into<String>) -> Self {
+        self.symbol = Some(symbol.into());
+        self
+    }
+
+    /// 複数行で表示する場合の二次元的な配置を指定する
+    pub fn layout(mut self, layout: Layout) -> Self {
+        self.layout = Some(layout);
+        self
+    }
+
+    /// 逆ポーランド記法やS式で使う記号
+    pub fn effective_symbol(&self) -> &str {
+        self.symbol.as_deref().unwrap_or(&self.name)
+    }
+
+    pub fn token(&self) -> Token {
+        self.op.token
+    }
+
+    /// 同じ表示になり、式を読み分けられないかどうか
+    fn is_ambiguous_with(&self, other: &BinaryOpDef) -> bool {
+        let same = |p: &BinaryOpPrinter, q: &BinaryOpPrinter| {
+            p.prefix == q.prefix && p.infix == q.infix && p.suffix == q.suffix
+        };
+        same(&self.printer, &other.printer)
+            || same(
+                &self.effective_unicode_printer(),
+                &other.effective_unicode_printer(),
+            )
+            || same(
+                &self.effective_ascii_printer(),
+                &other.effective_ascii_printer(),
+            )
+    }
+
+    /// Unicodeのスタイルで表示する場合に実際に使う表示方法
+    fn effective_unicode_printer(&self) -> BinaryOpPrinter {
+        match &self.unicode_printer {
+            Some(p) => self.derive(p),
+            None => self.printer.clone(),
+        }
+    }
+
+    /// ASCII文字だけで表示する場合に実際に使う表示方法
+    fn effective_ascii_printer(&self) -> BinaryOpPrinter {
+        let p = &self.printer;
+        match &self.ascii_printer {
+            Some(p) => self.derive(p),
+            None if (p.prefix.clone() + &p.infix + &p.suffix).is_ascii() => {
+                // 上付き・下付き文字は使わない
+                p.clone().scripts(Script::Normal, Script::Normal)
+            }
+            None => self.derive(&self.style(self.name.clone() + "(", ", ", ")", false, false)),
+        }
+    }
+
+    /// LaTeXで表示する場合に実際に使う表示方法
+    fn effective_latex_printer(&self) -> BinaryOpPrinter {
+        match &self.latex_printer {
+            Some(p) => self.derive(p),
+            None => self.effective_ascii_printer(),
+        }
+    }
+
+    /// MathMLで表示する場合に実際に使う表示方法
+    fn effective_mathml_printer(&self) -> BinaryOpPrinter {
+        let p = &self.printer;
+        match &self.mathml_printer {
+            Some(p) => self.derive(p),
+            None => BinaryOpPrinter {
+                swapped: p.swapped,
+                ..BinaryOpPrinter::new(
+                    p.token,
+                    mo(&p.prefix),
+                    mo(&p.infix),
+                    mo(&p.suffix),
+                    p.precedence,
+                    p.left_associative,
+                    p.right_associative,
+                    p.paren_left,
+                    p.paren_right,
+                )
+            },
+        }
+    }
+}
+
+/// 単項または二項演算子の定義
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum OpDef {
+    Unary(UnaryOpDef),
+    Binary(BinaryOpDef),
+}
+
+impl OpDef {
+    pub fn name(&self) -> &str {
+        match self {
+            OpDef::Unary(def) => &def.name,
+            OpDef::Binary(def) => &def.name,
+        }
+    }
+
+    pub fn token(&self) -> Token {
+        match self {
+            OpDef::Unary(def) => def.token(),
+            OpDef::Binary(def) => def.token(),
+        }
+    }
+}
+
+impl From<UnaryOpDef> for OpDef {
+    fn from(def: UnaryOpDef) -> Self {
+        OpDef::Unary(def)
+    }
+}
+
+impl From<BinaryOpDef> for OpDef {
+    fn from(def: BinaryOpDef) -> Self {
+        OpDef::Binary(def)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryError {
+    /// 同じトークンの演算子がすでに登録されている
+    DuplicateToken(Token),
+    /// 同じ名前の演算子がすでに登録されている
+    DuplicateName(String),
+    /// 桁や小数点のために予約されたトークンが使われている
+    ReservedToken(Token),
+    /// 指定された名前の演算子が登録されていない
+    UnknownOperator(String),
+    /// ユーザー定義の演算子に割り当てるトークンが残っていない
+    NoFreeToken,
+    /// 登録済みの演算子と同じ記号で表示される（べき乗とXORの`^`など）
+    AmbiguousSymbol { name: String, other: String },
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::DuplicateToken(token) => {
+                write!(f, "Token {:#04x} is already registered", token)
+            }
+            RegistryError::DuplicateName(name) => {
+                write!(f, "Operator named `{}` is already registered", name)
+            }
+            RegistryError::ReservedToken(token) => {
+                write!(
+                    f,
+                    "Token {:#04x} is reserved for digits and the decimal point",
+                    token
+                )
+            }
+            RegistryError::UnknownOperator(name) => {
+                write!(f, "Operator named `{}` is not registered", name)
+            }
+            RegistryError::NoFreeToken => write!(f, "No token is left for user-defined operators"),
+            RegistryError::AmbiguousSymbol { name, other } => write!(
+                f,
+                "Operator `{}` is printed with the same symbol as `{}`",
+                name, other
+            ),
+        }
+    }
+}
+
+impl error::Error for RegistryError {}
+
+/// 演算子定義の一覧。`Searcher`と`Printer`はここから作ることで対応が保証される
+#[derive(Debug, Clone, Default)]
+pub struct Registry {
+    unary_ops: Vec<UnaryOpDef>,
+    binary_ops: Vec<BinaryOpDef>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    /// 標準の演算子（`-x`, `√x`, `x!`, `+`, `-`, `*`, `/`, `^`）
+    pub fn standard() -> Registry {
+        use catalogue::*;
+        let mut registry = Registry::new();
+        for def in [negate(), sqrt(), fact()] {
+            registry.add_unary(def).unwrap();
+        }
+        for def in [add(), sub(), mul(), div(), pow()] {
+            registry.add_binary(def).unwrap();
+        }
+        registry
+    }
+
+    pub fn add(&mut self, def: impl Into<OpDef>) -> Result<(), RegistryError> {
+        match def.into() {
+            OpDef::Unary(def) => self.add_unary(def),
+            OpDef::Binary(def) => self.add_binary(def),
+        }
+    }
+
+    pub fn add_unary(&mut self, def: UnaryOpDef) -> Result<(), RegistryError> {
+        self.validate(def.token(), &def.name)?;
+        if let Some(other) = self.unary_ops.iter().find(|o| o.is_ambiguous_with(&def)) {
+            return Err(RegistryError::AmbiguousSymbol {
+                name: def.name,
+                other: other.name.clone(),
+            });
+        }
+        self.unary_ops.push(def);
+        Ok(())
+    }
+
+    pub fn add_binary(&mut self, def: BinaryOpDef) -> Result<(), RegistryError> {
+        self.validate(def.token(), &def.name)?;
+        if let Some(other) = self.binary_ops.iter().find(|o| o.is_ambiguous_with(&def)) {
+            return Err(RegistryError::AmbiguousSymbol {
+                name: def.name,
+                other: other.name.clone(),
+            });
+        }
+        self.binary_ops.push(def);
+        Ok(())
+    }
+
+    fn validate(&self, token: Token, name: &str) -> Result<(), RegistryError> {
+        if token >= DECIMAL_POINT {
+            return Err(RegistryError::ReservedToken(token));
+        }
+        if self.unary_ops.iter().any(|def| def.name == name)
+            || self.binary_ops.iter().any(|def| def.name == name)
+        {
+            return Err(RegistryError::DuplicateName(name.to_string()));
+        }
+        if self.contains_token(token) {
+            return Err(RegistryError::DuplicateToken(token));
+        }
+        Ok(())
+    }
+
+    fn contains_token(&self, token: Token) -> bool {
+        self.unary_ops.iter().any(|def| def.token() == token)
+            || self.binary_ops.iter().any(|def| def.token() == token)
+    }
+
+    /// ユーザー定義の演算子に割り当てられる未使用のトークン
+    pub fn next_user_token(&self) -> Result<Token, RegistryError> {
+        (USER_TOKEN_START..DECIMAL_POINT)
+            .find(|token| !self.contains_token(*token))
+            .ok_or(RegistryError::NoFreeToken)
+    }
+
+    /// 名前で指定した演算子の適用コストを変更する
+    pub fn set_cost(&mut self, name: &str, cost: u8) -> Result<(), RegistryError> {
+        if let Some(def) = self.unary_ops.iter_mut().find(|def| def.name == name) {
+            def.op.cost = cost;
+        } else if let Some(def) = self.binary_ops.iter_mut().find(|def| def.name == name) {
+            def.op.cost = cost;
+        } else {
+            return Err(RegistryError::UnknownOperator(name.to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn unary_ops(&self) -> &[UnaryOpDef] {
+        &self.unary_ops
+    }
+
+    pub fn binary_ops(&self) -> &[BinaryOpDef] {
+        &self.binary_ops
+    }
+
+    /// トークンから演算子名を引く
+    pub fn name(&self, token: Token) -> Option<&str> {
+        self.unary_ops
+            .iter()
+            .find(|def| def.token() == token)
+            .map(|def| def.name.as_str())
+            .or_else(|| {
+                self.binary_ops
+                    .iter()
+                    .find(|def| def.token() == token)
+                    .map(|def| def.name.as_str())
+            })
+    }
+
+    pub fn searcher(&self) -> Searcher {
+        Searcher::new(
+            self.unary_ops.iter().map(|def| def.op.clone()).collect(),
+            self.binary_ops.iter().map(|def| def.op.clone()).collect(),
+        )
+    }
+
+    pub fn printer(&self) -> Printer {
+        self.build_printer(
+            |def| def.printer.clone(),
+            |def| def.printer.clone(),
+            "(",
+            ")",
+        )
+    }
+
+    /// 指定したスタイルで表示する`Printer`
+    pub fn style_printer(&self, style: PrintStyle) -> Printer {
+        match style {
+            PrintStyle::Standard => self.printer(),
+            PrintStyle::Unicode => self.unicode_printer(),
+            PrintStyle::Ascii => self.ascii_printer(),
+        }
+    }
+
+    /// Unicodeのスタイルで表示する`Printer`。Unicode用の表示方法がない演算子は通常の表示方法を使う
+    pub fn unicode_printer(&self) -> Printer {
+        self.build_printer(
+            |def| def.effective_unicode_printer(),
+            |def| def.effective_unicode_printer(),
+            "(",
+            ")",
+        )
+    }
+
+    /// ASCII文字だけで表示する`Printer`。ASCII用の表示方法がない演算子は、
+    /// 通常の表示方法がASCII文字だけならそれを、そうでなければ演算子名を使った関数呼び出しの形を使う
+    pub fn ascii_printer(&self) -> Printer {
+        self.build_printer(
+            |def| def.effective_ascii_printer(),
+            |def| def.effective_ascii_printer(),
+            "(",
+            ")",
+        )
+    }
+
+    /// LaTeXで表示する`Printer`。LaTeX用の表示方法がない演算子はASCII文字だけの表示方法を使う
+    pub fn latex_printer(&self) -> Printer {
+        self.build_printer(
+            |def| def.effective_latex_printer(),
+            |def| def.effective_latex_printer(),
+            r"\left(",
+            r"\right)",
+        )
+    }
+
+    /// 逆ポーランド記法（`4 4 * 4 4 / +`）で表示する`SymbolicPrinter`
+    pub fn rpn_printer(&self) -> SymbolicPrinter {
+        self.symbolic_printer(SymbolicNotation::Rpn)
+    }
+
+    /// S式（`(+ (* 4 4) (/ 4 4))`）で表示する`SymbolicPrinter`
+    pub fn sexp_printer(&self) -> SymbolicPrinter {
+        self.symbolic_printer(SymbolicNotation::SExpression)
+    }
+
+    fn symbolic_printer(&self, notation: SymbolicNotation) -> SymbolicPrinter {
+        SymbolicPrinter::new(
+            notation,
+            self.unary_ops
+                .iter()
+                .map(|def| (def.token(), def.effective_symbol().to_string()))
+                .collect(),
+            self.binary_ops
+                .iter()
+                .map(|def| (def.token(), def.effective_symbol().to_string()))
+                .collect(),
+        )
+    }
+
+    /// 分数や根号、指数を複数行で表示する`PrettyPrinter`。
+    /// 二次元的な配置のない演算子は指定したスタイルの表示方法で1行に並べる
+    pub fn pretty_printer(&self, style: PrintStyle) -> PrettyPrinter {
+        let layouts = self
+            .unary_ops
+            .iter()
+            .filter_map(|def| Some((def.token(), def.layout.clone()?)))
+            .chain(
+                self.binary_ops
+                    .iter()
+                    .filter_map(|def| Some((def.token(), def.layout.clone()?))),
+            )
+            .collect();
+        PrettyPrinter::new(self.style_printer(style), layouts)
+    }
+
+    /// 構文木を作る`AstBuilder`
+    pub fn ast_builder(&self) -> AstBuilder {
+        let (unary_ops, binary_ops) = self.named_ops();
+        AstBuilder::new(unary_ops, binary_ops)
+    }
+
+    /// トークン列から値を計算し直す`Evaluator`
+    pub fn evaluator(&self) -> Evaluator {
+        let (unary_ops, binary_ops) = self.named_ops();
+        Evaluator::new(unary_ops, binary_ops)
+    }
+
+    /// 指定したスタイルで表示された式を読む`Parser`
+    pub fn parser(&self, style: PrintStyle) -> Parser {
+        let (unary_ops, binary_ops) = self.named_ops();
+        Parser::new(self.style_printer(style), unary_ops, binary_ops)
+    }
+
+    /// トークンと、演算子名および演算の組
+    #[allow(clippy::type_complexity)]
+    fn named_ops(
+        &self,
+    ) -> (
+        HashMap<Token, (String, UnaryOp)>,
+        HashMap<Token, (String, BinaryOp)>,
+    ) {
+        (
+            self.unary_ops
+                .iter()
+                .map(|def| (def.token(), (def.name.clone(), def.op.clone())))
+                .collect(),
+            self.binary_ops
+                .iter()
+                .map(|def| (def.token(), (def.name.clone(), def.op.clone())))
+                .collect(),
+        )
+    }
+
+    /// MathML（プレゼンテーションマークアップ）で表示する`Printer`。
+    /// MathML用の表示方法がない演算子は、通常の表示方法の記号を`<mo>`要素にして使う
+    pub fn mathml_printer(&self) -> Printer {
+        self.build_printer(
+            |def| def.effective_mathml_printer(),
+            |def| def.effective_mathml_printer(),
+            "<mo>(</mo>",
+            "<mo>)</mo>",
+        )
+        .numbers("<mn>", "</mn>")
+        .enclose(
+            r#"<math xmlns="http://www.w3.org/1998/Math/MathML"><mrow>"#,
+            "</mrow></math>",
+        )
+    }
+
+    fn build_printer(
+        &self,
+        unary: impl Fn(&UnaryOpDef) -> UnaryOpPrinter,
+        binary: impl Fn(&BinaryOpDef) -> BinaryOpPrinter,
+        paren_left: &str,
+        paren_right: &str,
+    ) -> Printer {
+        let unary_ops = self
+            .unary_ops
+            .iter()
+            .map(|def| def.op.clone())
+            .collect::<Vec<_>>();
+        let binary_ops = self
+            .binary_ops
+            .iter()
+            .map(|def| def.op.clone())
+            .collect::<Vec<_>>();
+        let binary_printers = self.binary_ops.iter().map(binary).collect::<Vec<_>>();
+        // 分数をそれ自体で範囲の明らかな形（`\frac{a}{b}`）で表示するなら、本来の優先度を伝える
+        let stacked = self
+            .binary_ops
+            .iter()
+            .zip(&binary_printers)
+            .filter(|(def, p)| def.layout == Some(Layout::Fraction) && p.precedence == 0)
+            .map(|(def, _)| (def.token(), def.printer.precedence))
+            .collect();
+        Printer::new(
+            self.unary_ops.iter().map(unary).collect(),
+            binary_printers,
+            paren_left,
+            paren_right,
+        )
+        .with_properties(&unary_ops, &binary_ops)
+        .stacked(stacked)
+        .validate(&unary_ops, &binary_ops)
+        .expect("every registered operator has exactly one printer")
+    }
+}
+
+/// 記号をMathMLの`<mo>`要素にする。空文字列はそのまま
+fn mo(symbol: &str) -> String {
+    if symbol.is_empty() {
+        return String::new();
+    }
+    let escaped = symbol
+        .trim()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    format!("<mo>{}</mo>", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        print::{Parenthesization, PrintError},
+        search::Equation,
+    };
+
+    fn equation(tokens: &[Token]) -> Equation {
+        Equation {
+            tokens: tokens.to_vec(),
+            cost: 0,
+            value: 0f64,
+        }
+    }
+
+    #[test]
+    fn style_printers_follow_standard_precedence() {
+        let registry = Registry::standard();
+        let print = |printer: Printer, tokens: &[Token]| printer.print(&equation(tokens)).unwrap();
+        // √(4!)
+        let tokens = [0xe4, 0x02, 0x01];
+        assert_eq!(print(registry.printer(), &tokens), "√(4!)");
+        assert_eq!(
+            print(registry.unicode_printer(), &tokens),
+            "√4\u{305}!\u{305}"
+        );
+        assert_eq!(print(registry.ascii_printer(), &tokens), "sqrt(4!)");
+        // (√4)^4
+        let tokens = [0xe4, 0x01, 0xe4, 0x14];
+        assert_eq!(print(registry.printer(), &tokens), "√4^4");
+        assert_eq!(print(registry.unicode_printer(), &tokens), "√4⁴");
+        assert_eq!(print(registry.ascii_printer(), &tokens), "sqrt(4)^4");
+    }
+
+    #[test]
+    fn latex_printer_follows_standard_precedence() {
+        // 4*(4-4)
+        let tokens = [0xe4, 0xe4, 0xe4, 0x11, 0x12];
+        let latex =
+            |registry: &Registry| registry.latex_printer().print(&equation(&tokens)).unwrap();
+        assert_eq!(latex(&Registry::standard()), r"4 \cdot \left(4-4\right)");
+
+        // 減算を乗算より強く結合させると、LaTeXでも括弧が要らなくなる
+        let mut registry = Registry::new();
+        let mut sub = catalogue::sub();
+        sub.printer.precedence = 4;
+        registry.add(sub).unwrap();
+        registry.add(catalogue::mul()).unwrap();
+        assert_eq!(latex(&registry), r"4 \cdot 4-4");
+    }
+
+    #[test]
+    fn latex_fractions_are_parenthesized_before_scripts_and_postfix_operators() {
+        let registry = Registry::standard();
+        let latex = |tokens: &[Token]| registry.latex_printer().print(&equation(tokens)).unwrap();
+        // (4/4)^4
+        assert_eq!(
+            latex(&[0xe4, 0xe4, 0x13, 0xe4, 0x14]),
+            r"\left(\frac{4}{4}\right)^{4}"
+        );
+        // (4/4)!
+        assert_eq!(
+            latex(&[0xe4, 0xe4, 0x13, 0x02]),
+            r"\left(\frac{4}{4}\right)!"
+        );
+        // 和、差、積の項や、前置の単項演算子の項では括弧は要らない
+        assert_eq!(
+            latex(&[0xe4, 0xe4, 0x13, 0xe4, 0xe4, 0x13, 0x11]),
+            r"\frac{4}{4}-\frac{4}{4}"
+        );
+        assert_eq!(
+            latex(&[0xe4, 0xe4, 0x13, 0xe4, 0xe4, 0x13, 0x12]),
+            r"\frac{4}{4} \cdot \frac{4}{4}"
+        );
+        assert_eq!(latex(&[0xe4, 0xe4, 0x13, 0x00]), r"-\frac{4}{4}");
+        // 4^(4/4)
+        assert_eq!(latex(&[0xe4, 0xe4, 0xe4, 0x13, 0x14]), r"4^{\frac{4}{4}}");
+    }
+
+    #[test]
+    fn associative_operators_omit_parentheses() {
+        let mut registry = Registry::new();
+        registry
+            .add(catalogue::bit_and(catalogue::BitWidth::default()))
+            .unwrap();
+        // 4&(4&4)
+        let e = equation(&[0xe4, 0xe4, 0xe4, 0x48, 0x48]);
+        let printer = registry.build_printer(
+            |def| def.printer.clone(),
+            |def| def.printer.clone(),
+            "(",
+            ")",
+        );
+        assert_eq!(printer.print(&e).unwrap(), "4&4&4");
+
+        // 代数的性質を使わなければ、右結合の指定のない`&`には括弧が付く
+        let printer = Printer::new(
+            vec![],
+            vec![catalogue::bit_and(catalogue::BitWidth::default()).printer],
+            "(",
+            ")",
+        );
+        assert_eq!(printer.print(&e).unwrap(), "4&(4&4)");
+    }
+
+    #[test]
+    fn parenthesizations_differ() {
+        let print = |registry: &Registry, parenthesization, tokens: &[Token]| {
+            registry
+                .printer()
+                .parenthesization(parenthesization)
+                .print(&equation(tokens))
+                .unwrap()
+        };
+        let registry = Registry::standard();
+        let prints = |tokens: &[Token]| {
+            [
+                Parenthesization::Minimal,
+                Parenthesization::Full,
+                Parenthesization::Semantic,
+            ]
+            .map(|parenthesization| print(&registry, parenthesization, tokens))
+        };
+        // -(4*4)+4
+        assert_eq!(
+            prints(&[0xe4, 0xe4, 0x12, 0x00, 0xe4, 0x10]),
+            ["-(4*4)+4", "(-(4*4))+4", "-4*4+4"]
+        );
+        // (-(4/4))*(4*4)
+        assert_eq!(
+            prints(&[0xe4, 0xe4, 0x13, 0x00, 0xe4, 0xe4, 0x12, 0x12]),
+            ["-(4/4)*4*4", "(-(4/4))*(4*4)", "-4/4*4*4"]
+        );
+        // 4+(4-4)
+        assert_eq!(
+            prints(&[0xe4, 0xe4, 0xe4, 0x11, 0x10]),
+            ["4+4-4", "4+(4-4)", "4+4-4"]
+        );
+        // 単項演算子を移した式も、より弱く結合する演算子の項では括弧で囲む
+        assert_eq!(
+            prints(&[0xe4, 0xe4, 0x12, 0x00, 0xe4, 0x14]),
+            ["(-(4*4))^4", "(-(4*4))^4", "(-4*4)^4"]
+        );
+        // 和や累乗には単項演算子を移せない
+        assert_eq!(
+            prints(&[0xe4, 0xe4, 0x10, 0x00]),
+            ["-(4+4)", "-(4+4)", "-(4+4)"]
+        );
+        assert_eq!(
+            prints(&[0xe4, 0xe4, 0x14, 0x00]),
+            ["-(4^4)", "-(4^4)", "-(4^4)"]
+        );
+
+        // 積と同じ優先度で単項演算子を移せない演算子があると、`-4%4*4`が`(-4)%4*4`と読まれうるので括弧を残す
+        let mut registry = Registry::standard();
+        registry.add(catalogue::rem()).unwrap();
+        assert_eq!(
+            print(
+                &registry,
+                Parenthesization::Semantic,
+                &[0xe4, 0xe4, 0x12, 0x00]
+            ),
+            "-(4*4)"
+        );
+    }
+
+    #[test]
+    fn malformed_token_sequences_are_rejected_by_every_printer() {
+        let registry = Registry::standard();
+        let print_all = |tokens: &[Token]| {
+            let e = equation(tokens);
+            [
+                registry.printer().print(&e),
+                registry.latex_printer().print(&e),
+                registry.rpn_printer().print(&e),
+                registry.pretty_printer(PrintStyle::Standard).print(&e),
+            ]
+        };
+        let cases = [
+            (vec![], PrintError::Empty),
+            (vec![0xe4, 0x10], PrintError::MissingOperand(1)),
+            (vec![0xf4], PrintError::MisplacedDigit(0)),
+            (vec![0xe4, 0x01, 0xf4], PrintError::MisplacedDigit(2)),
+            // 小数点の付いた数値には桁も小数点も続かない
+            (
+                vec![0xe4, DECIMAL_POINT, DECIMAL_POINT],
+                PrintError::MisplacedDigit(2),
+            ),
+            (
+                vec![0xe4, DECIMAL_POINT, 0xf4],
+                PrintError::MisplacedDigit(2),
+            ),
+            (vec![0xe4, 0xe4], PrintError::LeftoverOperands(1)),
+            (vec![0xe4, 0x7f], PrintError::UnknownToken(1, 0x7f)),
+        ];
+        for (tokens, error) in cases {
+            let expected = [(); 4].map(|_| Err(error.clone()));
+            assert_eq!(print_all(&tokens), expected, "{:x?}", tokens);
+        }
+    }
+
+    #[test]
+    fn printers_must_match_the_operators() {
+        let registry = Registry::standard();
+        let unary_ops = registry
+            .unary_ops()
+            .iter()
+            .map(|def| def.op.clone())
+            .collect::<Vec<_>>();
+        let binary_ops = registry
+            .binary_ops()
+            .iter()
+            .map(|def| def.op.clone())
+            .collect::<Vec<_>>();
+        assert!(registry.printer().validate(&unary_ops, &binary_ops).is_ok());
+
+        // 表示方法のない演算子
+        let lcm = catalogue::lcm();
+        let with_lcm = [binary_ops.clone(), vec![lcm.op.clone()]].concat();
+        assert_eq!(
+            registry.printer().validate(&unary_ops, &with_lcm).err(),
+            Some(PrintError::MissingOperator(lcm.token()))
+        );
+        // 演算子のない表示方法
+        let printer = Printer::new(vec![], vec![lcm.printer.clone()], "(", ")");
+        assert_eq!(
+            printer.validate(&[], &[]).err(),
+            Some(PrintError::UnknownOperator(lcm.token()))
+        );
+        // 1つの演算子に複数の表示方法
+        let printer = Printer::new(
+            vec![],
+            vec![lcm.printer.clone(), lcm.printer.clone()],
+            "(",
+            ")",
+        );
+        assert_eq!(
+            printer.validate(&[], std::slice::from_ref(&lcm.op)).err(),
+            Some(PrintError::DuplicateOperator(lcm.token()))
+        );
+        // 数値や小数点のトークン
+        let mut digit = lcm.printer.clone();
+        digit.token = 0xe4;
+        let printer = Printer::new(vec![], vec![digit], "(", ")");
+        assert_eq!(
+            printer.validate(&[], &[]).err(),
+            Some(PrintError::ReservedToken(0xe4))
+        );
+    }
+}
@@ -0,0 +1,751 @@
+//! TOML/JSON形式のルールファイルの読み込み
+//!
+//! ```toml
+//! preset = "classic"        # 省略時は standard
+//! concatenation = "decimal" # none, integer, decimal
+//! unary_rounds = 2
+//! prune_identity = true     # `x*1`や`x+0`を候補から除く（桁の消費に必要な式も除く）
+//!
+//! [[operators]]
+//! name = "add"
+//! cost = 2
+//!
+//! [[operators]]
+//! name = "mul"
+//! infix = "×"
+//! ```
+//!
+//! `operators`を指定した場合は、そこに挙げた演算子だけを組み込みの定義から選んで使う。
+//! `formula`を指定すると、組み込みの定義の代わりに式で定義した演算子を使う（`formula`モジュールを参照）。
+//!
+//! ```toml
+//! [[operators]]
+//! name = "triangle"
+//! formula = "T(x) = x*(x+1)/2 where x >= 0 && is_int(x)"
+//! cost = 5
+//! ```
+//!
+//! 階乗や二項係数などは`max_operand`で適用する数の上限を変更できる。
+//!
+//! ```toml
+//! [[operators]]
+//! name = "fact"
+//! max_operand = 12
+//! ```
+//!
+//! 優先度や結合性、括弧の指定はすべての表示スタイル（Unicode, ASCII, LaTeX, MathML）に適用する。
+//! 式で定義した中置記号の演算子（`a mod b = ...`）でも、既定の優先度と結合性（`Formula::INFIX_PRECEDENCE`）を変更できる。
+//! 記号（`prefix`, `infix`, `suffix`）を変更すると、UnicodeとASCIIのスタイルでもその記号を使う。
+//! LaTeXとMathMLでは組み込みの記法（`\cdot`や`<mfrac>`など）を使い続ける。
+//!
+//! ビット演算は`bits`で整数のビット幅（32または64）を指定できる。
+//! 記号が重なる演算子（`pow`と`xor`の`^`など）を同時に使う場合は、`infix`などで記号を変える。
+//!
+//! ```toml
+//! [[operators]]
+//! name = "xor"
+//! bits = 64
+//! infix = " xor "
+//! ```
+
+use crate::{
+    formula::{Formula, FormulaError},
+    preset::Preset,
+    registry::{
+        catalogue::{self, BitWidth},
+        OpDef, Registry, RegistryError,
+    },
+    search::{Concatenation, Value},
+};
+use serde::Deserialize;
+use std::{error, fmt};
+
+#[derive(Debug)]
+pub enum RulesError {
+    /// TOMLとして解釈できない
+    Toml(toml::de::Error),
+    /// JSONとして解釈できない
+    Json(serde_json::Error),
+    /// 存在しないプリセットが指定された
+    UnknownPreset(String),
+    /// 組み込みの定義にない演算子が指定された
+    UnknownOperator(String),
+    /// 演算子の種類に合わない項目が指定された（単項演算子の`infix`など）
+    InvalidField {
+        operator: String,
+        field: &'static str,
+    },
+    /// 項目の値が正しくない
+    InvalidValue {
+        operator: String,
+        field: &'static str,
+        value: String,
+    },
+    /// 演算子を定義する式が正しくない
+    Formula {
+        operator: String,
+        error: FormulaError,
+    },
+    Registry(RegistryError),
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RulesError::Toml(e) => write!(f, "Invalid TOML rules: {}", e),
+            RulesError::Json(e) => write!(f, "Invalid JSON rules: {}", e),
+            RulesError::UnknownPreset(name) => write!(
+                f,
+                "Unknown preset `{}` (available: {})",
+                name,
+                Preset::NAMES.join(", ")
+            ),
+            RulesError::UnknownOperator(name) => {
+                let names = catalogue::all()
+                    .iter()
+                    .map(|def| def.name().to_string())
+                    .collect::<Vec<_>>();
+                write!(
+                    f,
+                    "Unknown operator `{}` (available: {})",
+                    name,
+                    names.join(", ")
+                )
+            }
+            RulesError::InvalidField { operator, field } => {
+                write!(f, "Operator `{}` does not accept `{}`", operator, field)
+            }
+            RulesError::InvalidValue {
+                operator,
+                field,
+                value,
+            } => write!(
+                f,
+                "Invalid value `{}` for `{}` of operator `{}`",
+                value, field, operator
+            ),
+            RulesError::Formula { operator, error } => {
+                write!(f, "Invalid formula for operator `{}`: {}", operator, error)
+            }
+            RulesError::Registry(e) => e.fmt(f),
+        }
+    }
+}
+
+impl error::Error for RulesError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            RulesError::Toml(e) => Some(e),
+            RulesError::Json(e) => Some(e),
+            RulesError::Formula { error, .. } => Some(error),
+            RulesError::Registry(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<RegistryError> for RulesError {
+    fn from(e: RegistryError) -> Self {
+        RulesError::Registry(e)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    name: Option<String>,
+    preset: Option<String>,
+    concatenation: Option<ConcatenationRule>,
+    unary_rounds: Option<usize>,
+    max_value: Option<Value>,
+    max_cost: Option<u8>,
+    prune_identity: Option<bool>,
+    permute: Option<bool>,
+    target: Option<i32>,
+    operators: Option<Vec<OperatorRule>>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ConcatenationRule {
+    None,
+    Integer,
+    Decimal,
+}
+
+impl From<ConcatenationRule> for Concatenation {
+    fn from(rule: ConcatenationRule) -> Self {
+        match rule {
+            ConcatenationRule::None => Concatenation::None,
+            ConcatenationRule::Integer => Concatenation::Integer,
+            ConcatenationRule::Decimal => Concatenation::Decimal,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OperatorRule {
+    name: String,
+    formula: Option<String>,
+    cost: Option<u8>,
+    /// 適用する数の上限（階乗、二項係数など）
+    max_operand: Option<u32>,
+    /// ビット演算の整数のビット幅（32または64）
+    bits: Option<u32>,
+    precedence: Option<i32>,
+    /// 逆ポーランド記法やS式で使う記号
+    symbol: Option<String>,
+    prefix: Option<String>,
+    infix: Option<String>,
+    suffix: Option<String>,
+    paren: Option<bool>,
+    paren_left: Option<bool>,
+    paren_right: Option<bool>,
+    left_associative: Option<bool>,
+    right_associative: Option<bool>,
+}
+
+impl OperatorRule {
+    /// 式で定義した演算子に既定で設定するコスト
+    const FORMULA_COST: u8 = 5;
+
+    /// 組み込みの定義（または式による定義）を上書きした定義を返す
+    fn to_def(&self, registry: &Registry) -> Result<OpDef, RulesError> {
+        let invalid = |field| RulesError::InvalidField {
+            operator: self.name.clone(),
+            field,
+        };
+        let mut def = if let Some(formula) = &self.formula {
+            if self.max_operand.is_some() {
+                return Err(invalid("max_operand"));
+            }
+            if self.bits.is_some() {
+                return Err(invalid("bits"));
+            }
+            Formula::parse(formula)
+                .map_err(|error| RulesError::Formula {
+                    operator: self.name.clone(),
+                    error,
+                })?
+                .into_def(&self.name, registry.next_user_token()?, Self::FORMULA_COST)
+        } else {
+            let def = catalogue::find(&self.name)
+                .ok_or_else(|| RulesError::UnknownOperator(self.name.clone()))?;
+            let def = match self.max_operand {
+                Some(max) => {
+                    catalogue::find_up_to(&self.name, max).ok_or(invalid("max_operand"))?
+                }
+                None => def,
+            };
+            match self.bits {
+                Some(bits) => {
+                    let width = match bits {
+                        32 => BitWidth::W32,
+                        64 => BitWidth::W64,
+                        _ => {
+                            return Err(RulesError::InvalidValue {
+                                operator: self.name.clone(),
+                                field: "bits",
+                                value: bits.to_string(),
+                            })
+                        }
+                    };
+                    catalogue::find_with_width(&self.name, width).ok_or(invalid("bits"))?
+                }
+                None => def,
+            }
+        };
+        match &mut def {
+            OpDef::Unary(def) => {
+                let p = &mut def.printer;
+                if self.infix.is_some() {
+                    return Err(invalid("infix"));
+                }
+                if self.paren_left.is_some() {
+                    return Err(invalid("paren_left"));
+                }
+                if self.paren_right.is_some() {
+                    return Err(invalid("paren_right"));
+                }
+                if self.left_associative.is_some() {
+                    return Err(invalid("left_associative"));
+                }
+                if self.right_associative.is_some() {
+                    return Err(invalid("right_associative"));
+                }
+                def.op.cost = self.cost.unwrap_or(def.op.cost);
+                def.symbol = self.symbol.clone().or(def.symbol.clone());
+                p.precedence = self.precedence.unwrap_or(p.precedence);
+                if self.prefix.is_some() || self.suffix.is_some() {
+                    let prefix = self.prefix.clone().unwrap_or(p.prefix.clone());
+                    let suffix = self.suffix.clone().unwrap_or(p.suffix.clone());
+                    def.set_symbols(prefix, suffix);
+                }
+                if let Some(paren) = self.paren {
+                    def.set_paren(paren);
+                }
+            }
+            OpDef::Binary(def) => {
+                let p = &mut def.printer;
+                if self.paren.is_some() {
+                    return Err(invalid("paren"));
+                }
+                def.op.cost = self.cost.unwrap_or(def.op.cost);
+                def.symbol = self.symbol.clone().or(def.symbol.clone());
+                p.precedence = self.precedence.unwrap_or(p.precedence);
+                p.left_associative = self.left_associative.unwrap_or(p.left_associative);
+                p.right_associative = self.right_associative.unwrap_or(p.right_associative);
+                if self.prefix.is_some() || self.infix.is_some() || self.suffix.is_some() {
+                    let prefix = self.prefix.clone().unwrap_or(p.prefix.clone());
+                    let infix = self.infix.clone().unwrap_or(p.infix.clone());
+                    let suffix = self.suffix.clone().unwrap_or(p.suffix.clone());
+                    def.set_symbols(prefix, infix, suffix);
+                }
+                if let Some(paren) = self.paren_left {
+                    def.set_paren_left(paren);
+                }
+                if let Some(paren) = self.paren_right {
+                    def.set_paren_right(paren);
+                }
+            }
+        }
+        Ok(def)
+    }
+}
+
+impl RulesFile {
+    fn into_preset(self) -> Result<Preset, RulesError> {
+        let mut preset = match &self.preset {
+            Some(name) => {
+                Preset::by_name(name).ok_or_else(|| RulesError::UnknownPreset(name.clone()))?
+            }
+            None => Preset::standard(),
+        };
+        if let Some(operators) = &self.operators {
+            let mut registry = Registry::new();
+            for rule in operators {
+                let def = rule.to_def(&registry)?;
+                registry.add(def)?;
+            }
+            preset.registry = registry;
+        }
+        if let Some(name) = self.name {
+            preset.name = name;
+        }
+        if let Some(concatenation) = self.concatenation {
+            preset.concatenation = concatenation.into();
+        }
+        preset.unary_rounds = self.unary_rounds.unwrap_or(preset.unary_rounds);
+        preset.max_value = self.max_value.or(preset.max_value);
+        preset.max_cost = self.max_cost.or(preset.max_cost);
+        preset.prune_identity = self.prune_identity.unwrap_or(preset.prune_identity);
+        preset.permute = self.permute.unwrap_or(preset.permute);
+        preset.target = self.target.or(preset.target);
+        Ok(preset)
+    }
+}
+
+/// TOML形式のルールを読み込む
+pub fn from_toml(text: &str) -> Result<Preset, RulesError> {
+    toml::from_str::<RulesFile>(text)
+        .map_err(RulesError::Toml)?
+        .into_preset()
+}
+
+/// JSON形式のルールを読み込む
+pub fn from_json(text: &str) -> Result<Preset, RulesError> {
+    serde_json::from_str::<RulesFile>(text)
+        .map_err(RulesError::Json)?
+        .into_preset()
+}
+
+/// `{`で始まればJSON、そうでなければTOMLとしてルールを読み込む
+pub fn parse(text: &str) -> Result<Preset, RulesError> {
+    if text.trim_start().starts_with('{') {
+        from_json(text)
+    } else {
+        from_toml(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        print::PrintStyle,
+        registry::USER_TOKEN_START,
+        search::{Equation, Token},
+    };
+
+    #[test]
+    fn overrides_apply_to_every_style() {
+        let preset = from_toml(
+            r#"
+            [[operators]]
+            name = "sub"
+            infix = " minus "
+            precedence = 4
+
+            [[operators]]
+            name = "mul"
+            "#,
+        )
+        .unwrap();
+        // 4*(4-4)。減算が乗算より強く結合するので、どのスタイルでも括弧は要らない
+        let e = Equation {
+            tokens: vec![0xe4, 0xe4, 0xe4, 0x11, 0x12],
+            cost: 0,
+            value: 0f64,
+        };
+        let print = |printer: crate::print::Printer| printer.print(&e).unwrap();
+        assert_eq!(print(preset.printer()), "4*4 minus 4");
+        assert_eq!(
+            print(preset.style_printer(PrintStyle::Unicode)),
+            "4×4 minus 4"
+        );
+        assert_eq!(
+            print(preset.style_printer(PrintStyle::Ascii)),
+            "4*4 minus 4"
+        );
+        assert_eq!(print(preset.latex_printer()), r"4 \cdot 4 minus 4");
+        let parsed = preset
+            .parser(PrintStyle::Unicode)
+            .parse("4×4 minus 4")
+            .unwrap();
+        assert_eq!(parsed.value, 0f64);
+    }
+
+    #[test]
+    fn paren_overrides_apply_to_every_style() {
+        let preset = from_toml(
+            r#"
+            [[operators]]
+            name = "negate"
+            paren = false
+
+            [[operators]]
+            name = "add"
+            "#,
+        )
+        .unwrap();
+        // -(4+4)
+        let e = Equation {
+            tokens: vec![0xe4, 0xe4, 0x10, 0x00],
+            cost: 0,
+            value: 0f64,
+        };
+        let print = |printer: crate::print::Printer| printer.print(&e).unwrap();
+        assert_eq!(print(preset.printer()), "-4+4");
+        assert_eq!(print(preset.style_printer(PrintStyle::Unicode)), "−4+4");
+        assert_eq!(print(preset.latex_printer()), "-4+4");
+    }
+
+    #[test]
+    fn infix_formulas_take_precedence_and_associativity() {
+        let load = |overrides: &str| {
+            from_toml(&format!(
+                r#"
+                [[operators]]
+                name = "mul"
+
+                [[operators]]
+                name = "mod"
+                formula = "a mod b = a - b*floor(a/b) where b != 0"
+                {}
+                "#,
+                overrides
+            ))
+            .unwrap()
+        };
+        let print = |preset: &Preset, tokens: &[Token]| {
+            let e = Equation {
+                tokens: tokens.to_vec(),
+                cost: 0,
+                value: 0f64,
+            };
+            preset.printer().print(&e).unwrap()
+        };
+        let m = USER_TOKEN_START;
+        // (4*4) mod 4, (4 mod 4) mod 4
+        let product = [0xe4, 0xe4, 0x12, 0xe4, m];
+        let chain = [0xe4, 0xe4, m, 0xe4, m];
+
+        // 既定では乗算より強く結合し、結合性はない
+        let preset = load("");
+        assert_eq!(print(&preset, &product), "(4*4) mod 4");
+        assert_eq!(print(&preset, &chain), "(4 mod 4) mod 4");
+
+        let preset = load("precedence = 5\nleft_associative = true");
+        assert_eq!(print(&preset, &product), "4*4 mod 4");
+        assert_eq!(print(&preset, &chain), "4 mod 4 mod 4");
+        let parsed = preset
+            .parser(PrintStyle::Standard)
+            .parse("4*4 mod 4 mod 3")
+            .unwrap();
+        assert_eq!(parsed.tokens, [0xe4, 0xe4, 0x12, 0xe4, m, 0xe3, m]);
+        assert_eq!(parsed.value, 0f64);
+    }
+
+    /// TOMLとJSONの両方で読み込み、同じ結果になることを確かめて返す
+    fn load_both(toml: &str, json: &str) -> Preset {
+        let a = from_toml(toml).unwrap();
+        let b = parse(json).unwrap();
+        assert_eq!(a.name, b.name);
+        assert_eq!(a.concatenation, b.concatenation);
+        assert_eq!(a.unary_rounds, b.unary_rounds);
+        let names = |preset: &Preset| {
+            let registry = &preset.registry;
+            registry
+                .unary_ops()
+                .iter()
+                .map(|def| (def.name.clone(), def.op.cost))
+                .chain(
+                    registry
+                        .binary_ops()
+                        .iter()
+                        .map(|def| (def.name.clone(), def.op.cost)),
+                )
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&a), names(&b));
+        a
+    }
+
+    /// TOMLとJSONの両方で読み込みに失敗することを確かめ、それぞれのエラーを返す
+    fn errors(toml: &str, json: &str) -> [RulesError; 2] {
+        [from_toml(toml).unwrap_err(), parse(json).unwrap_err()]
+    }
+
+    #[test]
+    fn toml_and_json_rules() {
+        let preset = load_both(
+            r#"
+            name = "custom"
+            preset = "classic"
+            concatenation = "decimal"
+            unary_rounds = 2
+            max_cost = 40
+            prune_identity = true
+            permute = true
+            target = 10
+
+            [[operators]]
+            name = "fact"
+            max_operand = 5
+
+            [[operators]]
+            name = "add"
+            cost = 2
+
+            [[operators]]
+            name = "mul"
+            infix = "×"
+
+            [[operators]]
+            name = "triangle"
+            formula = "T(x) = x*(x+1)/2 where x >= 0 && is_int(x)"
+            "#,
+            r#"{
+                "name": "custom",
+                "preset": "classic",
+                "concatenation": "decimal",
+                "unary_rounds": 2,
+                "max_cost": 40,
+                "prune_identity": true,
+                "permute": true,
+                "target": 10,
+                "operators": [
+                    {"name": "fact", "max_operand": 5},
+                    {"name": "add", "cost": 2},
+                    {"name": "mul", "infix": "×"},
+                    {
+                        "name": "triangle",
+                        "formula": "T(x) = x*(x+1)/2 where x >= 0 && is_int(x)"
+                    }
+                ]
+            }"#,
+        );
+        assert_eq!(preset.name, "custom");
+        assert_eq!(preset.concatenation, Concatenation::Decimal);
+        assert_eq!(preset.unary_rounds, 2);
+        assert_eq!(preset.max_cost, Some(40));
+        assert!(preset.prune_identity);
+        assert!(preset.permute);
+        assert_eq!(preset.target, Some(10));
+
+        let registry = &preset.registry;
+        let unary = registry.unary_ops();
+        assert_eq!(unary.len(), 2);
+        assert_eq!(unary[0].name, "fact");
+        assert_eq!(unary[1].name, "triangle");
+        assert_eq!(unary[1].op.cost, OperatorRule::FORMULA_COST);
+        // 5! までしか適用しない
+        assert!((unary[0].op.apply)(5f64).is_some());
+        assert!((unary[0].op.apply)(6f64).is_none());
+        let binary = registry.binary_ops();
+        assert_eq!(binary.len(), 2);
+        assert_eq!((binary[0].name.as_str(), binary[0].op.cost), ("add", 2));
+        assert_eq!(binary[1].printer.infix, "×");
+
+        // 演算子を指定しなければプリセットの演算子を使う
+        let preset = load_both("preset = \"integer\"", r#"{"preset": "integer"}"#);
+        assert_eq!(
+            preset.registry.binary_ops().len(),
+            Preset::integer().registry.binary_ops().len()
+        );
+    }
+
+    #[test]
+    fn unknown_names() {
+        for e in errors("preset = \"chess\"", r#"{"preset": "chess"}"#) {
+            assert!(matches!(&e, RulesError::UnknownPreset(name) if name == "chess"));
+        }
+
+        for e in errors(
+            "[[operators]]\nname = \"cube\"",
+            r#"{"operators": [{"name": "cube"}]}"#,
+        ) {
+            assert!(matches!(&e, RulesError::UnknownOperator(name) if name == "cube"));
+        }
+    }
+
+    #[test]
+    fn duplicate_operators() {
+        // トークンはルールファイルでは指定できず、名前ごとに決まるので、重複は名前の重複として報告する
+        for e in errors(
+            "[[operators]]\nname = \"add\"\n[[operators]]\nname = \"add\"",
+            r#"{"operators": [{"name": "add"}, {"name": "add"}]}"#,
+        ) {
+            assert!(matches!(
+                &e,
+                RulesError::Registry(RegistryError::DuplicateName(name)) if name == "add"
+            ));
+        }
+
+        // 式で定義した演算子と同じ名前の組み込みの演算子
+        for e in errors(
+            "[[operators]]\nname = \"sqrt\"\n\
+             [[operators]]\nname = \"sqrt\"\nformula = \"r(x) = x^.5\"",
+            r#"{"operators": [{"name": "sqrt"}, {"name": "sqrt", "formula": "r(x) = x^.5"}]}"#,
+        ) {
+            assert!(matches!(
+                &e,
+                RulesError::Registry(RegistryError::DuplicateName(name)) if name == "sqrt"
+            ));
+        }
+
+        // 式で定義した演算子には、重ならないトークンを割り当てる
+        let preset = load_both(
+            "[[operators]]\nname = \"f\"\nformula = \"f(x) = x+1\"\n\
+             [[operators]]\nname = \"g\"\nformula = \"g(x) = x+2\"",
+            r#"{"operators": [
+                {"name": "f", "formula": "f(x) = x+1"},
+                {"name": "g", "formula": "g(x) = x+2"}
+            ]}"#,
+        );
+        let unary = preset.registry.unary_ops();
+        assert_ne!(unary[0].token(), unary[1].token());
+
+        // 同じ記号の演算子
+        for e in errors(
+            "[[operators]]\nname = \"add\"\n[[operators]]\nname = \"sub\"\ninfix = \"+\"",
+            r#"{"operators": [{"name": "add"}, {"name": "sub", "infix": "+"}]}"#,
+        ) {
+            assert!(matches!(
+                &e,
+                RulesError::Registry(RegistryError::AmbiguousSymbol { name, other })
+                    if name == "sub" && other == "add"
+            ));
+        }
+    }
+
+    #[test]
+    fn invalid_overrides() {
+        let cases = [
+            ("fact", "infix = \"!\"", r#""infix": "!""#, "infix"),
+            (
+                "fact",
+                "paren_left = true",
+                r#""paren_left": true"#,
+                "paren_left",
+            ),
+            (
+                "negate",
+                "right_associative = true",
+                r#""right_associative": true"#,
+                "right_associative",
+            ),
+            ("add", "paren = true", r#""paren": true"#, "paren"),
+            (
+                "add",
+                "max_operand = 10",
+                r#""max_operand": 10"#,
+                "max_operand",
+            ),
+            ("add", "bits = 32", r#""bits": 32"#, "bits"),
+        ];
+        for (name, toml, json, field) in cases {
+            for e in errors(
+                &format!("[[operators]]\nname = \"{}\"\n{}", name, toml),
+                &format!(r#"{{"operators": [{{"name": "{}", {}}}]}}"#, name, json),
+            ) {
+                assert!(
+                    matches!(
+                        &e,
+                        RulesError::InvalidField { operator, field: f }
+                            if operator == name && *f == field
+                    ),
+                    "{}",
+                    e
+                );
+            }
+        }
+
+        for e in errors(
+            "[[operators]]\nname = \"and\"\nbits = 16",
+            r#"{"operators": [{"name": "and", "bits": 16}]}"#,
+        ) {
+            assert!(matches!(
+                &e,
+                RulesError::InvalidValue { operator, field: "bits", value }
+                    if operator == "and" && value == "16"
+            ));
+        }
+
+        for e in errors(
+            "[[operators]]\nname = \"f\"\nformula = \"f(x) = x+\"",
+            r#"{"operators": [{"name": "f", "formula": "f(x) = x+"}]}"#,
+        ) {
+            assert!(matches!(&e, RulesError::Formula { operator, .. } if operator == "f"));
+        }
+    }
+
+    #[test]
+    fn malformed_files() {
+        // 構文の誤り、未知の項目、型の誤り
+        let cases = [
+            ("preset = ", r#"{"preset": }"#),
+            ("colour = \"red\"", r#"{"colour": "red"}"#),
+            (
+                "[[operators]]\nname = \"add\"\nweight = 1",
+                r#"{"operators": [{"name": "add", "weight": 1}]}"#,
+            ),
+            ("concatenation = \"roman\"", r#"{"concatenation": "roman"}"#),
+            ("unary_rounds = -1", r#"{"unary_rounds": -1}"#),
+        ];
+        for (toml, json) in cases {
+            assert!(
+                matches!(from_toml(toml), Err(RulesError::Toml(_))),
+                "{}",
+                toml
+            );
+            assert!(
+                matches!(from_json(json), Err(RulesError::Json(_))),
+                "{}",
+                json
+            );
+            assert!(matches!(parse(json), Err(RulesError::Json(_))), "{}", json);
+        }
+    }
+}
@@ -1,20 +1,41 @@
 mod binary_op;
 mod equation;
+mod rational;
+mod stats;
 mod unary_op;
 
 pub type Value = f64;
 /// 演算子および桁トークン。
 /// `0xe0`より後の値は桁のために予約済みで、
 /// - `t = 0xe0 + n (0 <= n < 10)`
-/// または
+///   または
 /// - `t = 0xf0 + n (0 <= n < 10)`
 ///
 /// のとき、数字`n`を表す。
 /// `0xeX`は先頭桁を示し、`0xfX`は残りの桁を示す。
+/// また`0xdf`は小数点（`DECIMAL_POINT`）として予約済み。
 pub type Token = u8;
+/// 直前の数字列の先頭に小数点を付けることを表すトークン（`4`, `4`, `DECIMAL_POINT`で`.44`）
+pub const DECIMAL_POINT: Token = 0xdf;
+
+/// 数字を連結して多桁の数として扱うかどうか
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Concatenation {
+    /// 連結しない（数字はすべて一桁の数として使う）
+    None,
+    /// 整数として連結する（`44`）
+    #[default]
+    Integer,
+    /// 整数に加えて小数点付きの数も使う（`44`, `.44`）
+    Decimal,
+}
 #[cfg(not(feature = "with-rayon"))]
 use std::collections::hash_map::Entry;
-use std::{collections::HashMap, hash};
+use std::{
+    collections::{HashMap, HashSet},
+    hash,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 pub use binary_op::*;
 
@@ -22,8 +43,13 @@ pub use binary_op::*;
 use dashmap::{mapref::entry::Entry, DashMap};
 pub use equation::*;
 use fnv::FnvBuildHasher;
+pub use rational::*;
 #[cfg(feature = "with-rayon")]
-use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
+};
+use stats::Stopwatch;
+pub use stats::*;
 pub use unary_op::*;
 
 #[cfg(feature = "with-rayon")]
@@ -57,38 +83,180 @@ impl Eq for WrappedValue {}
 pub struct Searcher {
     unary_ops: Vec<UnaryOp>,
     binary_ops: Vec<BinaryOp>,
+    /// 適用しても意味のない（外側の演算, 内側の演算）の組
+    redundant_unary: HashSet<(Token, Token)>,
+    /// 単位元との演算を省略するかどうか
+    prune_identity: bool,
+    concatenation: Concatenation,
+    /// 単項演算を繰り返し適用する回数
+    unary_rounds: usize,
+    /// 保持する値の絶対値の上限
+    max_value: Option<Value>,
+    /// 保持する式のコストの上限
+    max_cost: Option<u8>,
 }
 
 impl Searcher {
     pub fn new(unary_ops: Vec<UnaryOp>, binary_ops: Vec<BinaryOp>) -> Searcher {
+        let mut redundant_unary = HashSet::new();
+        for op in unary_ops.iter() {
+            if op.involution || op.idempotent {
+                redundant_unary.insert((op.token, op.token));
+            }
+            if let Some(inverse) = op.inverse {
+                redundant_unary.insert((inverse, op.token));
+            }
+        }
         Searcher {
             unary_ops,
             binary_ops,
+            redundant_unary,
+            prune_identity: false,
+            concatenation: Concatenation::default(),
+            unary_rounds: 3,
+            max_value: None,
+            max_cost: None,
+        }
+    }
+
+    /// 数字の連結方法を指定する
+    pub fn concatenation(mut self, concatenation: Concatenation) -> Self {
+        self.concatenation = concatenation;
+        self
+    }
+
+    /// 単項演算を繰り返し適用する回数を指定する（デフォルトは3回）
+    pub fn unary_rounds(mut self, rounds: usize) -> Self {
+        self.unary_rounds = rounds;
+        self
+    }
+
+    /// 保持する値の絶対値の上限を指定する。上限を超える値（および非有限値）は捨てる
+    pub fn max_value(mut self, max_value: Option<Value>) -> Self {
+        self.max_value = max_value;
+        self
+    }
+
+    /// 保持する式のコストの上限を指定する。上限を超える式は捨てる
+    pub fn max_cost(mut self, max_cost: Option<u8>) -> Self {
+        self.max_cost = max_cost;
+        self
+    }
+
+    /// 値とコストが上限の範囲内かどうか
+    fn is_within_limits(&self, e: &Equation) -> bool {
+        self.max_value
+            .is_none_or(|max| e.value.is_finite() && e.value.abs() <= max)
+            && self.max_cost.is_none_or(|max| e.cost <= max)
+    }
+
+    /// `x * 1`や`x + 0`のような単位元との演算を候補から除くかどうかを指定する（デフォルトは除かない）。
+    ///
+    /// 単位元との演算は値を変えないが、桁を消費する手段としては必要になることがある（`4 + 4 * (4 - 4)`など）。
+    /// 有効にすると、そのような式が見つからなくなったりコストが高くなったりする
+    /// （`4444`では`4 = 4+4*(4-4)`が`4 = √(4+4+4+4)`になる）。
+    /// 単位元との組み合わせは各部分列で項の数に比例する程度しかないので、探索もほとんど速くならない。
+    /// 桁の消費に必要な場合だけを残す枝刈りはまだないため、既定では無効にしている
+    pub fn prune_identity(mut self, prune: bool) -> Self {
+        self.prune_identity = prune;
+        self
+    }
+
+    /// 演算子の性質から、より安い同値の式が必ず存在するとわかる単項演算の適用かどうか
+    fn is_redundant_unary(&self, op: &UnaryOp, e: &Equation) -> bool {
+        self.redundant_unary.contains(&(op.token, e.last_token()))
+    }
+
+    /// 演算子の性質から、より安い同値の式が存在するとわかる二項演算の適用かどうか。
+    /// `min_cost1`, `min_cost2`はそれぞれの項の属する部分列で最も安い式のコスト
+    fn is_redundant_binary(
+        &self,
+        op: &BinaryOp,
+        (e1, min_cost1): (&Equation, u8),
+        (e2, min_cost2): (&Equation, u8),
+    ) -> bool {
+        // 吸収元との演算は相手によらず同じ値になるので、最も安い相手とだけ組み合わせればよい。
+        // ただし`lcm`などは最も安い相手に適用できないことがあるので、すべての項に適用できる演算に限る
+        let absorbed = |z: &Equation, x: &Equation, min_cost: u8| {
+            op.total && op.absorbing == Some(z.value) && x.cost > min_cost
+        };
+        let identity = |e: &Equation| self.prune_identity && op.identity == Some(e.value);
+        absorbed(e2, e1, min_cost1)
+            || identity(e2)
+            || op.commutative && (absorbed(e1, e2, min_cost2) || identity(e1))
+    }
+
+    fn apply_unary(&self, op: &UnaryOp, e: &Equation, pruned: &AtomicUsize) -> Option<Equation> {
+        if self.is_redundant_unary(op, e) {
+            pruned.fetch_add(1, Ordering::Relaxed);
+            return None;
         }
+        Equation::apply_unary(e, op).filter(|e| self.is_within_limits(e))
+    }
+
+    fn apply_binary(
+        &self,
+        op: &BinaryOp,
+        e1: (&Equation, u8),
+        e2: (&Equation, u8),
+        pruned: &AtomicUsize,
+    ) -> Option<Equation> {
+        if self.is_redundant_binary(op, e1, e2) {
+            pruned.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        Equation::apply_binary(e1.0, e2.0, op).filter(|e| self.is_within_limits(e))
     }
 
     pub fn search(&self, memo: &mut HashMap<String, Knowledge>, numbers: &str) {
+        self.search_with_stats(memo, numbers, &mut SearchStats::default());
+    }
+
+    /// `search`と同様に探索し、部分列ごとの統計を`stats`に記録する
+    pub fn search_with_stats(
+        &self,
+        memo: &mut HashMap<String, Knowledge>,
+        numbers: &str,
+        stats: &mut SearchStats,
+    ) {
         if memo.contains_key(numbers) {
             return;
         }
         log::info!("Start searching for {}", numbers);
 
-        #[cfg(feature = "with-rayon")]
-        let knowledge = Knowledge::default();
-        #[cfg(not(feature = "with-rayon"))]
         let mut knowledge = Knowledge::default();
+        let mut s = SubstringStats::default();
 
         // 数値単独での表現
-        let e = Equation::from_numbers(numbers);
-        knowledge.insert(WrappedValue(e.value), e);
+        if numbers.len() == 1 || self.concatenation != Concatenation::None {
+            let e = Equation::from_numbers(numbers);
+            knowledge.insert(WrappedValue(e.value), e);
+        }
+        if self.concatenation == Concatenation::Decimal {
+            let e = Equation::from_decimal(numbers);
+            merge(&mut knowledge, e, &mut s);
+        }
 
         for i in 1..numbers.len() {
             let (key_left, key_right) = numbers.split_at(i);
-            self.search(memo, key_left);
-            self.search(memo, key_right);
+            self.search_with_stats(memo, key_left, stats);
+            self.search_with_stats(memo, key_right, stats);
         }
 
+        // 各二項演算子の適用回数
+        let attempts = (1..numbers.len())
+            .map(|i| {
+                let (key_left, key_right) = numbers.split_at(i);
+                memo[key_left].len() * memo[key_right].len()
+            })
+            .sum::<usize>();
+        // 演算子ごとの適用成功回数
+        let mut applied_count = HashMap::<Token, usize>::new();
+        // 演算子ごとの枝刈り回数
+        let pruned = new_counters(self.binary_ops.len());
+
         log::debug!("Combining...");
+        let stopwatch = Stopwatch::start();
         cfg_if::cfg_if! {
             if #[cfg(feature = "with-rayon")] {
                 let combined =  (1..numbers.len())
@@ -97,96 +265,179 @@ impl Searcher {
                         let (key_left, key_right) = numbers.split_at(i);
                         let knowledge_left = &memo[key_left];
                         let knowledge_right = &memo[key_right];
-                        self.binary_ops.par_iter().flat_map(move |op| {
+                        let min_left = min_cost(knowledge_left);
+                        let min_right = min_cost(knowledge_right);
+                        self.binary_ops.par_iter().zip(pruned.par_iter()).flat_map(move |(op, pruned)| {
                             knowledge_left.par_iter().flat_map(move |r1| {
-                                knowledge_right
-                                    .par_iter()
-                                    .filter_map(move |r2| Equation::apply_binary(r1.value(), r2.value(), op))
+                                knowledge_right.par_iter().filter_map(move |r2| {
+                                    self.apply_binary(op, (r1.value(), min_left), (r2.value(), min_right), pruned)
+                                })
                             })
                         })
                     })
                     .collect::<Vec<_>>();
+                s.combine_time = stopwatch.elapsed();
                 log::debug!("Merging...");
+                let stopwatch = Stopwatch::start();
                 for equation in combined {
-                    match knowledge.entry(WrappedValue(equation.value)) {
-                        Entry::Occupied(mut o) => {
-                            if o.get().cost > equation.cost {
-                                o.insert(equation);
-                            }
-                        }
-                        Entry::Vacant(v) => {
-                            v.insert(equation);
-                        }
-                    }
+                    *applied_count.entry(equation.last_token()).or_default() += 1;
+                    merge(&mut knowledge, equation, &mut s);
                 }
+                s.merge_time = stopwatch.elapsed();
             } else {
                 (1..numbers.len())
                     .flat_map(|i| {
                         let (key_left, key_right) = numbers.split_at(i);
                         let knowledge_left = &memo[key_left];
                         let knowledge_right = &memo[key_right];
-                        self.binary_ops.iter().flat_map(move |op| {
-                            knowledge_left.iter().flat_map(move |(_, e1)| {
-                                knowledge_right
-                                    .iter()
-                                    .filter_map(move |(_, e2)| Equation::apply_binary(e1, e2, op))
+                        let min_left = min_cost(knowledge_left);
+                        let min_right = min_cost(knowledge_right);
+                        self.binary_ops.iter().zip(pruned.iter()).flat_map(move |(op, pruned)| {
+                            knowledge_left.values().flat_map(move |e1| {
+                                knowledge_right.values().filter_map(move |e2| {
+                                    self.apply_binary(op, (e1, min_left), (e2, min_right), pruned)
+                                })
                             })
                         })
                     }).for_each(|equation| {
-                        match knowledge.entry(WrappedValue(equation.value)) {
-                            Entry::Occupied(mut o) => {
-                                if o.get().cost > equation.cost {
-                                    o.insert(equation);
-                                }
-                            }
-                            Entry::Vacant(v) => {
-                                v.insert(equation);
-                            }
-                        }
-                    })
+                        *applied_count.entry(equation.last_token()).or_default() += 1;
+                        merge(&mut knowledge, equation, &mut s);
+                    });
+                // 組み合わせとマージを同時に行うので、まとめて組み合わせの時間とする
+                s.combine_time = stopwatch.elapsed();
             }
         }
+        s.candidates += attempts * self.binary_ops.len();
+        for (op, pruned) in self.binary_ops.iter().zip(pruned) {
+            let pruned = pruned.into_inner();
+            let applied = applied_count.get(&op.token).copied().unwrap_or(0);
+            *s.rejected.entry(op.token).or_default() += attempts - applied - pruned;
+            *s.pruned.entry(op.token).or_default() += pruned;
+        }
 
-        // 単項演算で拡大する（３回まで）
-        for i in 0..3 {
+        // 単項演算で拡大する（`unary_rounds`回まで）
+        for i in 0..self.unary_rounds {
             log::info!("Start applying unary ops to {} - {}", numbers, i + 1);
+            let stopwatch = Stopwatch::start();
+            let attempts = knowledge.len();
+            let pruned = new_counters(self.unary_ops.len());
             cfg_if::cfg_if! {
                 if #[cfg(feature = "with-rayon")] {
                     let applied = self.unary_ops
                     .par_iter()
-                    .flat_map(|op| {
+                    .zip(pruned.par_iter())
+                    .flat_map(|(op, pruned)| {
                         knowledge
                             .par_iter()
-                            .filter_map(move |r| Equation::apply_unary(r.value(), op))
+                            .filter_map(move |r| self.apply_unary(op, r.value(), pruned))
                     })
                     .collect::<Vec<_>>();
                 } else {
                     let applied = self.unary_ops
                     .iter()
-                    .flat_map(|op| {
+                    .zip(pruned.iter())
+                    .flat_map(|(op, pruned)| {
                         knowledge
-                            .iter()
-                            .filter_map(move |(_, e)| Equation::apply_unary(e, op))
+                            .values()
+                            .filter_map(move |e| self.apply_unary(op, e, pruned))
                     })
                     .collect::<Vec<_>>();
                 }
             }
+            s.candidates += attempts * self.unary_ops.len();
+            let mut applied_count = HashMap::<Token, usize>::new();
+            for equation in applied.iter() {
+                *applied_count.entry(equation.last_token()).or_default() += 1;
+            }
+            for (op, pruned) in self.unary_ops.iter().zip(pruned) {
+                let pruned = pruned.into_inner();
+                let applied = applied_count.get(&op.token).copied().unwrap_or(0);
+                *s.rejected.entry(op.token).or_default() += attempts - applied - pruned;
+                *s.pruned.entry(op.token).or_default() += pruned;
+            }
             log::debug!("Merging...");
+            let len = knowledge.len();
             for equation in applied {
-                match knowledge.entry(WrappedValue(equation.value)) {
-                    Entry::Occupied(mut o) => {
-                        if o.get().cost > equation.cost {
-                            o.insert(equation);
-                        }
-                    }
-                    Entry::Vacant(v) => {
-                        v.insert(equation);
-                    }
-                }
+                merge(&mut knowledge, equation, &mut s);
             }
+            s.unary_added.push(knowledge.len() - len);
+            s.unary_time.push(stopwatch.elapsed());
         }
 
         log::info!("End searching for {}", numbers);
+        s.entries = knowledge.len();
+        stats.substrings.push((numbers.to_string(), s));
         memo.insert(numbers.to_string(), knowledge);
     }
 }
+
+fn new_counters(n: usize) -> Vec<AtomicUsize> {
+    (0..n).map(|_| AtomicUsize::new(0)).collect()
+}
+
+/// 部分列の中で最も安い式のコスト
+fn min_cost(knowledge: &Knowledge) -> u8 {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "with-rayon")] {
+            knowledge.iter().map(|r| r.cost).min().unwrap_or(0)
+        } else {
+            knowledge.values().map(|e| e.cost).min().unwrap_or(0)
+        }
+    }
+}
+
+/// 同じ値を持つ既存のエントリがなければ追加し、あればコストの低い方を残す
+fn merge(knowledge: &mut Knowledge, equation: Equation, stats: &mut SubstringStats) {
+    match knowledge.entry(WrappedValue(equation.value)) {
+        Entry::Occupied(mut o) => {
+            if o.get().cost > equation.cost {
+                o.insert(equation);
+                stats.replacements += 1;
+            }
+        }
+        Entry::Vacant(v) => {
+            v.insert(equation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::catalogue;
+
+    #[test]
+    fn absorbing_elements_of_partial_ops_are_combined_with_every_operand() {
+        let lcm = catalogue::lcm().op;
+        let mul = catalogue::mul().op;
+        let searcher = Searcher::new(vec![], vec![lcm.clone(), mul.clone()]);
+        // 連結なしの部分列`48`で最も安い式が`4/8`だとしても、`lcm`は整数にしか適用できない
+        let half = Equation {
+            tokens: vec![0xe4, 0xe8, 0x13],
+            cost: 1,
+            value: 0.5f64,
+        };
+        let product = Equation {
+            tokens: vec![0xe4, 0xe8, 0x12],
+            cost: 2,
+            value: 32f64,
+        };
+        let zero = Equation::from_numbers("0");
+        let pruned = AtomicUsize::new(0);
+
+        assert!(searcher
+            .apply_binary(&lcm, (&half, half.cost), (&zero, zero.cost), &pruned)
+            .is_none());
+        let e = searcher
+            .apply_binary(&lcm, (&product, half.cost), (&zero, zero.cost), &pruned)
+            .unwrap();
+        assert_eq!(e.value, 0f64);
+        assert_eq!(pruned.load(Ordering::Relaxed), 0);
+
+        // すべての項に適用できる演算では、最も安い相手以外との組み合わせを省略する
+        assert!(searcher
+            .apply_binary(&mul, (&product, half.cost), (&zero, zero.cost), &pruned)
+            .is_none());
+        assert_eq!(pruned.load(Ordering::Relaxed), 1);
+    }
+}
@@ -1,21 +1,125 @@
-use super::{Token, Value};
+use super::{Rational, Token, Value};
+use std::{fmt, sync::Arc};
 
-#[derive(Debug, Clone, Copy)]
+/// 二項演算の本体。パラメータなどを捕捉できるようにクロージャとして持つ
+pub type BinaryFn = Arc<dyn Fn(Value, Value) -> Option<Value> + Send + Sync>;
+/// 有理数での厳密な計算。厳密に求められない場合は`None`
+pub type ExactBinaryFn = Arc<dyn Fn(Rational, Rational) -> Option<Rational> + Send + Sync>;
+
+#[derive(Clone)]
 pub struct BinaryOp {
     /// トークン
     pub token: Token,
     /// 適用コスト
     pub cost: u8,
     /// 適用
-    pub apply: fn(Value, Value) -> Option<Value>,
+    pub apply: BinaryFn,
+    /// 有理数での厳密な適用。`None`なら厳密には計算できない演算
+    pub exact: Option<ExactBinaryFn>,
+    /// 可換（`a ∘ b == b ∘ a`）かどうか
+    pub commutative: bool,
+    /// 結合的（`(a ∘ b) ∘ c == a ∘ (b ∘ c)`）かどうか
+    pub associative: bool,
+    /// 右単位元（`a ∘ e == a`となる`e`）。可換なら左単位元でもある
+    pub identity: Option<Value>,
+    /// 右吸収元（`a ∘ z == z`となる`z`）。可換なら左吸収元でもある
+    pub absorbing: Option<Value>,
+    /// すべての項に適用できる（`apply`が`None`を返さない）かどうか
+    pub total: bool,
+    /// この演算を打ち消す演算（`(a ∘ b) ∙ b == a`となる`∙`）のトークン
+    pub inverse: Option<Token>,
 }
 
 impl BinaryOp {
-    pub fn new(token: Token, cost: u8, apply: fn(Value, Value) -> Option<Value>) -> Self {
-        BinaryOp { token, cost, apply }
+    pub fn new(
+        token: Token,
+        cost: u8,
+        apply: impl Fn(Value, Value) -> Option<Value> + Send + Sync + 'static,
+    ) -> Self {
+        BinaryOp {
+            token,
+            cost,
+            apply: Arc::new(apply),
+            exact: None,
+            commutative: false,
+            associative: false,
+            identity: None,
+            absorbing: None,
+            total: false,
+            inverse: None,
+        }
+    }
+
+    /// 可換であることを指定する
+    pub fn commutative(mut self) -> Self {
+        self.commutative = true;
+        self
+    }
+
+    /// 結合的であることを指定する
+    pub fn associative(mut self) -> Self {
+        self.associative = true;
+        self
+    }
+
+    /// 右単位元を指定する
+    pub fn identity(mut self, e: Value) -> Self {
+        self.identity = Some(e);
+        self
+    }
+
+    /// 右吸収元を指定する
+    pub fn absorbing(mut self, z: Value) -> Self {
+        self.absorbing = Some(z);
+        self
+    }
+
+    /// すべての項に適用できることを指定する
+    pub fn total(mut self) -> Self {
+        self.total = true;
+        self
+    }
+
+    /// この演算を打ち消す演算を指定する
+    pub fn inverse(mut self, token: Token) -> Self {
+        self.inverse = Some(token);
+        self
+    }
+
+    /// 有理数での厳密な計算を指定する。`apply`と同じ値を厳密に求めること
+    pub fn exact(
+        mut self,
+        exact: impl Fn(Rational, Rational) -> Option<Rational> + Send + Sync + 'static,
+    ) -> Self {
+        self.exact = Some(Arc::new(exact));
+        self
+    }
+
+    /// 整数の項に対して`apply`が浮動小数点の誤差なく整数を返す演算（階乗やビット演算など）であることを指定する。
+    /// 項と結果が浮動小数点で正確に表せる整数の場合は、`apply`の結果を厳密な値とする
+    pub fn exact_on_integers(self) -> Self {
+        let apply = self.apply.clone();
+        self.exact(move |x, y| {
+            Rational::from_value(apply(x.to_exact_value()?, y.to_exact_value()?)?)
+        })
     }
 
     pub(super) fn apply(&self, lhs: Value, rhs: Value) -> Option<Value> {
         (self.apply)(lhs, rhs)
     }
 }
+
+impl fmt::Debug for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BinaryOp")
+            .field("token", &self.token)
+            .field("cost", &self.cost)
+            .field("commutative", &self.commutative)
+            .field("associative", &self.associative)
+            .field("identity", &self.identity)
+            .field("absorbing", &self.absorbing)
+            .field("total", &self.total)
+            .field("inverse", &self.inverse)
+            .finish_non_exhaustive()
+    }
+}
@@ -1,4 +1,4 @@
-use super::{BinaryOp, Token, UnaryOp, Value};
+use super::{BinaryOp, Token, UnaryOp, Value, DECIMAL_POINT};
 
 #[derive(Debug, Clone)]
 /// 等式に関する知識。トークン列の計算結果がある値と一致することを示す。
@@ -10,29 +10,34 @@ pub struct Equation {
 
 impl Equation {
     /// `Equation`に単項演算子を適用して新しい`Equation`を作成
-    pub(super) fn apply_unary(e: &Equation, op: &UnaryOp) -> Option<Equation> {
+    pub(crate) fn apply_unary(e: &Equation, op: &UnaryOp) -> Option<Equation> {
         let value = op.apply(e.value)?;
         let mut tokens = e.tokens.clone();
         tokens.push(op.token);
         Some(Equation {
             tokens,
-            cost: e.cost + op.cost,
+            cost: e.cost.saturating_add(op.cost),
             value,
         })
     }
 
-    pub(super) fn apply_binary(e1: &Equation, e2: &Equation, op: &BinaryOp) -> Option<Equation> {
+    pub(crate) fn apply_binary(e1: &Equation, e2: &Equation, op: &BinaryOp) -> Option<Equation> {
         let value = op.apply(e1.value, e2.value)?;
         let mut tokens = e1.tokens.clone();
         tokens.append(&mut e2.tokens.clone());
         tokens.push(op.token);
         Some(Equation {
             tokens,
-            cost: e1.cost + e2.cost + op.cost,
+            cost: e1.cost.saturating_add(e2.cost).saturating_add(op.cost),
             value,
         })
     }
 
+    /// 最後に適用された演算子（演算子がなければ最後の桁）のトークン
+    pub(super) fn last_token(&self) -> Token {
+        *self.tokens.last().unwrap()
+    }
+
     /// 探索数値列からその全体を唯一の値として持つ`Equation`を作成
     pub fn from_numbers(numbers: &str) -> Equation {
         let mut tokens: Vec<Token> = numbers
@@ -48,4 +53,14 @@ impl Equation {
             value,
         }
     }
+
+    /// 探索数値列の先頭に小数点を付けた数（`.44`など）を唯一の値として持つ`Equation`を作成
+    pub fn from_decimal(numbers: &str) -> Equation {
+        let mut e = Equation::from_numbers(numbers);
+        e.tokens.push(DECIMAL_POINT);
+        // 小数点を使う分だけわずかにコストをかける
+        e.cost = 1;
+        e.value = format!("0.{}", numbers).parse().unwrap();
+        e
+    }
 }
@@ -0,0 +1,187 @@
+use super::Value;
+use std::fmt;
+
+/// 浮動小数点で正確に表せる整数の絶対値の上限
+const MAX_EXACT_INTEGER: i128 = 1 << 53;
+
+/// 有理数。途中の値が`i128`に収まらない場合は、各演算が`None`を返す
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rational {
+    numerator: i128,
+    /// 常に正で、分子と互いに素
+    denominator: i128,
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// `x`の`n`乗根が整数なら返す
+fn integer_root(x: i128, n: u32) -> Option<i128> {
+    if x < 0 {
+        return None;
+    }
+    let estimate = (x as f64).powf(1f64 / n as f64).round() as i128;
+    (estimate.saturating_sub(1)..=estimate.saturating_add(1))
+        .find(|r| *r >= 0 && r.checked_pow(n) == Some(x))
+}
+
+impl Rational {
+    /// 分母が0の場合や、符号を反転できない`i128::MIN`を含む場合は`None`
+    pub fn new(numerator: i128, denominator: i128) -> Option<Rational> {
+        if denominator == 0 || numerator == i128::MIN || denominator == i128::MIN {
+            return None;
+        }
+        let g = gcd(numerator, denominator);
+        let sign = denominator.signum();
+        Some(Rational {
+            numerator: numerator / g * sign,
+            denominator: denominator / g * sign,
+        })
+    }
+
+    pub fn integer(n: i128) -> Rational {
+        Rational {
+            numerator: n,
+            denominator: 1,
+        }
+    }
+
+    /// 浮動小数点で正確に表せる範囲の整数なら、その値の有理数
+    pub fn from_value(v: Value) -> Option<Rational> {
+        let n = v as i128;
+        (v.fract() == 0f64 && n.abs() <= MAX_EXACT_INTEGER).then(|| Rational::integer(n))
+    }
+
+    pub fn numerator(&self) -> i128 {
+        self.numerator
+    }
+
+    pub fn denominator(&self) -> i128 {
+        self.denominator
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.denominator == 1
+    }
+
+    /// 浮動小数点で正確に表せる範囲の整数なら、その値
+    pub fn to_exact_value(&self) -> Option<Value> {
+        (self.is_integer() && self.numerator.abs() <= MAX_EXACT_INTEGER)
+            .then_some(self.numerator as Value)
+    }
+
+    pub fn to_value(&self) -> Value {
+        self.numerator as Value / self.denominator as Value
+    }
+
+    pub fn checked_add(&self, other: &Rational) -> Option<Rational> {
+        Rational::new(
+            self.numerator
+                .checked_mul(other.denominator)?
+                .checked_add(other.numerator.checked_mul(self.denominator)?)?,
+            self.denominator.checked_mul(other.denominator)?,
+        )
+    }
+
+    pub fn checked_sub(&self, other: &Rational) -> Option<Rational> {
+        self.checked_add(&other.checked_neg()?)
+    }
+
+    pub fn checked_mul(&self, other: &Rational) -> Option<Rational> {
+        Rational::new(
+            self.numerator.checked_mul(other.numerator)?,
+            self.denominator.checked_mul(other.denominator)?,
+        )
+    }
+
+    /// 0で割る場合は`None`
+    pub fn checked_div(&self, other: &Rational) -> Option<Rational> {
+        Rational::new(
+            self.numerator.checked_mul(other.denominator)?,
+            self.denominator.checked_mul(other.numerator)?,
+        )
+    }
+
+    pub fn checked_neg(&self) -> Option<Rational> {
+        Some(Rational {
+            numerator: self.numerator.checked_neg()?,
+            denominator: self.denominator,
+        })
+    }
+
+    /// 整数乗。負の指数なら逆数の累乗
+    pub fn checked_pow(&self, exponent: i32) -> Option<Rational> {
+        let n = exponent.unsigned_abs();
+        let r = Rational::new(
+            self.numerator.checked_pow(n)?,
+            self.denominator.checked_pow(n)?,
+        )?;
+        if exponent < 0 {
+            Rational::integer(1).checked_div(&r)
+        } else {
+            Some(r)
+        }
+    }
+
+    /// `n`乗根が有理数なら返す。`n`が奇数なら負の数の根も求める
+    pub fn root(&self, n: u32) -> Option<Rational> {
+        if n == 0 {
+            return None;
+        }
+        if self.numerator < 0 {
+            return if n % 2 == 1 {
+                self.checked_neg()?.root(n)?.checked_neg()
+            } else {
+                None
+            };
+        }
+        Rational::new(
+            integer_root(self.numerator, n)?,
+            integer_root(self.denominator, n)?,
+        )
+    }
+
+    /// 累乗。指数が整数でなければ、分母の数の累乗根が有理数の場合のみ求める（`4^.5`）
+    pub fn checked_powr(&self, exponent: &Rational) -> Option<Rational> {
+        let numerator = i32::try_from(exponent.numerator).ok()?;
+        let denominator = u32::try_from(exponent.denominator).ok()?;
+        self.root(denominator)?.checked_pow(numerator)
+    }
+
+    pub fn floor(&self) -> Rational {
+        Rational::integer(self.numerator.div_euclid(self.denominator))
+    }
+
+    pub fn ceil(&self) -> Option<Rational> {
+        self.checked_neg()?.floor().checked_neg()
+    }
+
+    /// 四捨五入。ちょうど半分の場合は0から遠い方に丸める（`Value::round`と同じ）
+    pub fn round(&self) -> Option<Rational> {
+        let doubled = Rational::new(
+            self.numerator
+                .checked_abs()?
+                .checked_mul(2)?
+                .checked_add(self.denominator)?,
+            self.denominator.checked_mul(2)?,
+        )?;
+        Some(Rational::integer(
+            doubled.floor().numerator * self.numerator.signum(),
+        ))
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_integer() {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use super::Token;
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+/// 部分列ひとつ分の探索統計
+#[derive(Debug, Clone, Default)]
+pub struct SubstringStats {
+    /// 生成した候補の数（演算子の適用回数）
+    pub candidates: usize,
+    /// 最終的に保持したエントリ数
+    pub entries: usize,
+    /// 既存のエントリをよりコストの低い式で置き換えた回数
+    pub replacements: usize,
+    /// 単項演算の各ラウンドで新たに追加されたエントリ数
+    pub unary_added: Vec<usize>,
+    /// 演算子ごとの適用失敗（`None`）の回数
+    pub rejected: BTreeMap<Token, usize>,
+    /// 演算子の性質から不要と判断して省略した候補の数
+    pub pruned: BTreeMap<Token, usize>,
+    /// 二項演算による組み合わせにかかった時間
+    pub combine_time: Duration,
+    /// 組み合わせ結果のマージにかかった時間
+    pub merge_time: Duration,
+    /// 単項演算の各ラウンドにかかった時間
+    pub unary_time: Vec<Duration>,
+}
+
+/// 探索全体の統計
+#[derive(Debug, Clone, Default)]
+pub struct SearchStats {
+    /// 部分列ごとの統計（探索が完了した順）
+    pub substrings: Vec<(String, SubstringStats)>,
+}
+
+impl SearchStats {
+    pub fn get(&self, numbers: &str) -> Option<&SubstringStats> {
+        self.substrings
+            .iter()
+            .find(|(n, _)| n == numbers)
+            .map(|(_, s)| s)
+    }
+}
+
+impl fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<10} {:>12} {:>10} {:>10} {:>10} {:>10} {:>10}  unary added / rejected / pruned",
+            "numbers", "candidates", "entries", "replaced", "combine", "merge", "unary"
+        )?;
+        for (numbers, s) in self.substrings.iter() {
+            let unary_time = s.unary_time.iter().sum::<Duration>();
+            let unary_added = s
+                .unary_added
+                .iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+            let rejected = s
+                .rejected
+                .iter()
+                .map(|(token, n)| format!("{:#04x}={}", token, n))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let pruned = s
+                .pruned
+                .iter()
+                .filter(|(_, n)| **n > 0)
+                .map(|(token, n)| format!("{:#04x}={}", token, n))
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(
+                f,
+                "{:<10} {:>12} {:>10} {:>10} {:>10.2?} {:>10.2?} {:>10.2?}  [{}] {} / {}",
+                numbers,
+                s.candidates,
+                s.entries,
+                s.replacements,
+                s.combine_time,
+                s.merge_time,
+                unary_time,
+                unary_added,
+                rejected,
+                pruned
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// 経過時間の計測。wasmでは`std::time::Instant`が使えないので`Date.now()`を使う
+pub(super) struct Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    start: std::time::Instant,
+    #[cfg(target_arch = "wasm32")]
+    start: f64,
+}
+
+impl Stopwatch {
+    pub(super) fn start() -> Stopwatch {
+        Stopwatch {
+            #[cfg(not(target_arch = "wasm32"))]
+            start: std::time::Instant::now(),
+            #[cfg(target_arch = "wasm32")]
+            start: js_sys::Date::now(),
+        }
+    }
+
+    pub(super) fn elapsed(&self) -> Duration {
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "wasm32")] {
+                Duration::from_secs_f64((js_sys::Date::now() - self.start).max(0f64) / 1000f64)
+            } else {
+                self.start.elapsed()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{registry::Registry, search_int_by, search_int_permuted};
+
+    /// 部分列ごとの統計が互いに矛盾しないことを確かめる
+    fn assert_consistent(stats: &SearchStats, unary_ops: usize, binary_ops: usize) {
+        for (numbers, s) in &stats.substrings {
+            // 部分列は一度だけ探索する
+            assert_eq!(
+                stats
+                    .substrings
+                    .iter()
+                    .filter(|(n, _)| n == numbers)
+                    .count(),
+                1,
+                "{}",
+                numbers
+            );
+            // 二項演算の候補は、分割した左右の部分列のエントリ数の積
+            let attempts = (1..numbers.len())
+                .map(|i| {
+                    let (left, right) = numbers.split_at(i);
+                    stats.get(left).unwrap().entries * stats.get(right).unwrap().entries
+                })
+                .sum::<usize>();
+            // 単項演算の候補は、各ラウンドを始める時点のエントリ数
+            let mut len = s.entries - s.unary_added.iter().sum::<usize>();
+            let mut unary_attempts = 0;
+            for added in &s.unary_added {
+                unary_attempts += len;
+                len += added;
+            }
+            assert_eq!(
+                s.candidates,
+                attempts * binary_ops + unary_attempts * unary_ops,
+                "{}",
+                numbers
+            );
+
+            // 候補は適用に成功したもの、失敗したもの、枝刈りしたもののいずれか
+            let rejected = s.rejected.values().sum::<usize>();
+            let pruned = s.pruned.values().sum::<usize>();
+            assert!(rejected + pruned <= s.candidates, "{}", numbers);
+            let applied = s.candidates - rejected - pruned;
+            // 新しい値と置き換えはすべて適用に成功した候補から生じる
+            assert!(
+                s.entries - 1 + s.replacements <= applied,
+                "{}: {} entries, {} replacements, {} applied",
+                numbers,
+                s.entries,
+                s.replacements,
+                applied
+            );
+        }
+    }
+
+    #[test]
+    fn candidates_are_rejected_pruned_or_applied() {
+        let registry = Registry::standard();
+        let searcher = registry.searcher().prune_identity(true);
+        for numbers in ["44", "444"] {
+            let (_, stats) = search_int_by(&searcher, numbers);
+            assert_consistent(
+                &stats,
+                registry.unary_ops().len(),
+                registry.binary_ops().len(),
+            );
+            let names = stats
+                .substrings
+                .iter()
+                .map(|(n, _)| n.as_str())
+                .collect::<Vec<_>>();
+            // 短い部分列から順に探索が完了する
+            assert_eq!(names, ["4", "44", "444"][..numbers.len()]);
+            let s = stats.get(numbers).unwrap();
+            assert!(s.pruned.values().sum::<usize>() > 0);
+            assert!(s.rejected.values().sum::<usize>() > 0);
+        }
+    }
+
+    #[test]
+    fn permuted_search_shares_substring_stats() {
+        let registry = Registry::standard();
+        let searcher = registry.searcher();
+        let (_, merged) = search_int_permuted(&searcher, "123");
+        assert_consistent(
+            &merged,
+            registry.unary_ops().len(),
+            registry.binary_ops().len(),
+        );
+        // すべての並べ替えの部分列を含む
+        for numbers in ["1", "2", "3", "12", "21", "23", "32", "13", "31"] {
+            assert!(merged.get(numbers).is_some(), "{}", numbers);
+        }
+        assert_eq!(merged.substrings.len(), 3 + 6 + 6);
+        // 並べ替えの間で共有した部分列の統計は、単独で探索した場合と同じ
+        for numbers in ["12", "231", "312"] {
+            let (_, alone) = search_int_by(&searcher, numbers);
+            for (n, s) in &alone.substrings {
+                let m = merged.get(n).unwrap();
+                assert_eq!(
+                    (m.candidates, m.entries, m.unary_added.clone()),
+                    (s.candidates, s.entries, s.unary_added.clone()),
+                    "{}",
+                    n
+                );
+            }
+        }
+    }
+}
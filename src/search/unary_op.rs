@@ -1,21 +1,103 @@
-use super::{Token, Value};
+use super::{Rational, Token, Value};
+use std::{fmt, sync::Arc};
 
-#[derive(Debug, Clone, Copy)]
+/// 単項演算の本体。パラメータなどを捕捉できるようにクロージャとして持つ
+pub type UnaryFn = Arc<dyn Fn(Value) -> Option<Value> + Send + Sync>;
+/// 有理数での厳密な計算。厳密に求められない場合は`None`
+pub type ExactUnaryFn = Arc<dyn Fn(Rational) -> Option<Rational> + Send + Sync>;
+
+#[derive(Clone)]
 pub struct UnaryOp {
     /// トークン
     pub token: Token,
     /// 適用コスト
     pub cost: u8,
     /// 適用
-    pub apply: fn(Value) -> Option<Value>,
+    pub apply: UnaryFn,
+    /// 有理数での厳密な適用。`None`なら厳密には計算できない演算
+    pub exact: Option<ExactUnaryFn>,
+    /// 対合（`f(f(x)) == x`）かどうか
+    pub involution: bool,
+    /// 冪等（`f(f(x)) == f(x)`）かどうか
+    pub idempotent: bool,
+    /// この演算を打ち消す演算（`g(f(x)) == x`となる`g`）のトークン
+    pub inverse: Option<Token>,
+    /// この演算を左の項に移せる（`f(a ∘ b) == f(a) ∘ b`となる）二項演算`∘`のトークン
+    pub moves_into: Vec<Token>,
 }
 
 impl UnaryOp {
-    pub fn new(token: Token, cost: u8, apply: fn(Value) -> Option<Value>) -> Self {
-        UnaryOp { token, cost, apply }
+    pub fn new(
+        token: Token,
+        cost: u8,
+        apply: impl Fn(Value) -> Option<Value> + Send + Sync + 'static,
+    ) -> Self {
+        UnaryOp {
+            token,
+            cost,
+            apply: Arc::new(apply),
+            exact: None,
+            involution: false,
+            idempotent: false,
+            inverse: None,
+            moves_into: vec![],
+        }
+    }
+
+    /// 対合であることを指定する
+    pub fn involution(mut self) -> Self {
+        self.involution = true;
+        self
+    }
+
+    /// 冪等であることを指定する
+    pub fn idempotent(mut self) -> Self {
+        self.idempotent = true;
+        self
+    }
+
+    /// この演算を打ち消す演算を指定する。`inverse(f(x)) == x`が`f`の定義域全体で成り立つこと
+    pub fn inverse(mut self, token: Token) -> Self {
+        self.inverse = Some(token);
+        self
+    }
+
+    /// この演算を左の項に移せる二項演算を指定する。`f(a ∘ b) == f(a) ∘ b`がすべての項で成り立つこと
+    pub fn moves_into(mut self, token: Token) -> Self {
+        self.moves_into.push(token);
+        self
+    }
+
+    /// 有理数での厳密な計算を指定する。`apply`と同じ値を厳密に求めること
+    pub fn exact(
+        mut self,
+        exact: impl Fn(Rational) -> Option<Rational> + Send + Sync + 'static,
+    ) -> Self {
+        self.exact = Some(Arc::new(exact));
+        self
+    }
+
+    /// 整数の項に対して`apply`が浮動小数点の誤差なく整数を返す演算（階乗やビット演算など）であることを指定する。
+    /// 項と結果が浮動小数点で正確に表せる整数の場合は、`apply`の結果を厳密な値とする
+    pub fn exact_on_integers(self) -> Self {
+        let apply = self.apply.clone();
+        self.exact(move |x| Rational::from_value(apply(x.to_exact_value()?)?))
     }
 
     pub(super) fn apply(&self, value: Value) -> Option<Value> {
         (self.apply)(value)
     }
 }
+
+impl fmt::Debug for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnaryOp")
+            .field("token", &self.token)
+            .field("cost", &self.cost)
+            .field("involution", &self.involution)
+            .field("idempotent", &self.idempotent)
+            .field("inverse", &self.inverse)
+            .field("moves_into", &self.moves_into)
+            .finish_non_exhaustive()
+    }
+}